@@ -0,0 +1,65 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use image::{DynamicImage, GenericImageView, ImageBuffer, ImageOutputFormat, Rgba, RgbaImage};
+use ptonlineres2prpr::{hit_fx_convector, hit_fx_grid, hit_fx_grid_image, ImageOptions};
+use std::hint::black_box;
+use std::io::Cursor;
+
+const FRAME_SIZE: u32 = 512;
+const FRAME_COUNT: u32 = 30;
+
+/// A vertical strip of 30 512x512 frames, like a 4K hit effect.
+fn synthetic_strip() -> RgbaImage {
+    RgbaImage::from_fn(FRAME_SIZE, FRAME_SIZE * FRAME_COUNT, |x, y| {
+        Rgba([x as u8, y as u8, (x ^ y) as u8, (y / FRAME_SIZE * 8) as u8])
+    })
+}
+
+/// The per-pixel copy the blit-based layout replaced, reading the decoded image through
+/// `GenericImageView` as the old converter did. Kept as a baseline.
+fn per_pixel_grid(img: &DynamicImage) -> RgbaImage {
+    let (columns, rows) = hit_fx_grid(FRAME_COUNT);
+    let mut grid = ImageBuffer::new(FRAME_SIZE * columns, FRAME_SIZE * rows);
+
+    for i in 0..FRAME_COUNT {
+        let new_x = (i % columns) * FRAME_SIZE;
+        let new_y = (i / columns) * FRAME_SIZE;
+        for y in 0..FRAME_SIZE {
+            for x in 0..FRAME_SIZE {
+                grid.put_pixel(new_x + x, new_y + y, img.get_pixel(x, i * FRAME_SIZE + y));
+            }
+        }
+    }
+    grid
+}
+
+/// The whole old conversion: decode, copy pixel by pixel, encode.
+fn per_pixel_convector(png: &[u8]) -> Vec<u8> {
+    let grid = per_pixel_grid(&image::load_from_memory(png).unwrap());
+    let mut output = Vec::new();
+    grid.write_to(&mut Cursor::new(&mut output), ImageOutputFormat::Png).unwrap();
+    output
+}
+
+fn bench_hit_fx(c: &mut Criterion) {
+    let strip = synthetic_strip();
+    let decoded = DynamicImage::ImageRgba8(strip.clone());
+
+    let mut layout = c.benchmark_group("hit_fx_layout");
+    layout.sample_size(20);
+    layout.bench_function("per_pixel", |b| b.iter(|| per_pixel_grid(black_box(&decoded))));
+    layout.bench_function("blit", |b| b.iter(|| hit_fx_grid_image(black_box(&strip), Some(FRAME_COUNT)).unwrap()));
+    layout.finish();
+
+    let mut png = Vec::new();
+    strip.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+    let options = ImageOptions::default();
+
+    let mut convector = c.benchmark_group("hit_fx_convector");
+    convector.sample_size(10);
+    convector.bench_function("per_pixel", |b| b.iter(|| per_pixel_convector(black_box(&png))));
+    convector.bench_function("blit", |b| b.iter(|| hit_fx_convector(black_box(&png), Some(FRAME_COUNT), &options).unwrap()));
+    convector.finish();
+}
+
+criterion_group!(benches, bench_hit_fx);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::ConvertError;
+use crate::meta::{builtin_res_type, ResType};
+
+const IMAGE_EXTENSIONS: [&str; 9] = ["png", "jpg", "jpeg", "webp", "gif", "bmp", "avif", "tga", "tiff"];
+const AUDIO_EXTENSIONS: [&str; 8] = ["ogg", "oga", "mp3", "wav", "flac", "m4a", "aac", "opus"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResKind {
+    Image,
+    Audio,
+}
+
+impl ResKind {
+    pub(crate) fn allows(self, res_type: &ResType) -> bool {
+        matches!(
+            (self, res_type),
+            (ResKind::Image, ResType::Image(_)) | (ResKind::Audio, ResType::Audio(_))
+        )
+    }
+}
+
+/// Lowercases a resource name and strips a known image or audio extension, returning
+/// the kind of resource that extension belongs to.
+pub(crate) fn normalize_res_name(name: &str) -> (String, Option<ResKind>) {
+    let lower = name.trim().to_lowercase();
+    match lower.rsplit_once('.') {
+        Some((stem, ext)) if IMAGE_EXTENSIONS.contains(&ext) => (stem.to_string(), Some(ResKind::Image)),
+        Some((stem, ext)) if AUDIO_EXTENSIONS.contains(&ext) => (stem.to_string(), Some(ResKind::Audio)),
+        _ => (lower, None),
+    }
+}
+
+/// Extra `res` key names, keyed by the PhiTogether name they stand for, e.g. `tap: [note_tap]`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ResAliases(BTreeMap<String, Vec<String>>);
+
+impl ResAliases {
+    /// Loads aliases from a TOML file (`.toml`) or a YAML file (anything else).
+    pub fn load(path: &Path) -> Result<ResAliases, ConvertError> {
+        let data = fs::read_to_string(path)?;
+        let is_toml = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let aliases: ResAliases = if is_toml {
+            toml::from_str(&data).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str(&data).map_err(|e| e.to_string())
+        }
+        .map_err(|e| ConvertError::Validation(format!("Invalid alias config {}: {}", path.display(), e)))?;
+
+        if let Some(name) = aliases.0.keys().find(|name| builtin_res_type(&normalize_res_name(name).0).is_none()) {
+            return Err(ConvertError::Validation(format!(
+                "Invalid alias config {}: unknown resource {}",
+                path.display(), name
+            )));
+        }
+        Ok(aliases)
+    }
+
+    pub fn insert(&mut self, res_name: &str, alias: &str) {
+        self.0.entry(res_name.to_string()).or_default().push(alias.to_string());
+    }
+
+    pub fn merge(mut self, overrides: ResAliases) -> ResAliases {
+        for (res_name, aliases) in overrides.0 {
+            self.0.entry(res_name).or_default().extend(aliases);
+        }
+        self
+    }
+
+    /// Finds the resource a normalized name is an alias of, with the alias that matched.
+    pub(crate) fn find(&self, stem: &str) -> Option<(ResType, &str)> {
+        self.0.iter().find_map(|(res_name, aliases)| {
+            let alias = aliases.iter().find(|alias| normalize_res_name(alias).0 == stem)?;
+            builtin_res_type(&normalize_res_name(res_name).0).map(|res_type| (res_type, alias.as_str()))
+        })
+    }
+}
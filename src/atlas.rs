@@ -0,0 +1,714 @@
+use image::codecs::jpeg::JpegDecoder;
+use image::codecs::png::PngDecoder;
+use image::{ColorType, DynamicImage, GenericImage, ImageBuffer, ImageDecoder, ImageEncoder, ImageFormat, ImageOutputFormat, Pixel, Rgba, RgbaImage};
+use rayon::prelude::*;
+use std::io::Cursor;
+
+use crate::error::ConvertError;
+use crate::meta::ImageResType;
+
+pub(crate) const PRPR_DEFAULT_HIT_FX: (u32, u32) = (5, 6);
+const PT_DEFAULT_HIT_FX_FRAMES: u32 = 30;
+
+type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+type Buffer<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+/// The `holdend`, `hold` and `holdhead` PNGs cut from a hold atlas.
+type HoldPiecePngs = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+/// How source images are interpreted and written when they are composited or re-encoded.
+#[derive(Debug, Clone, Default)]
+pub struct ImageOptions {
+    /// Treat sources as having premultiplied alpha and convert them to straight alpha,
+    /// which PNG requires.
+    pub premultiplied_alpha: bool,
+    /// Write 16-bit PNGs when a source has 16 bits per channel, instead of reducing to 8.
+    pub preserve_16bit: bool,
+}
+
+struct HitFxStrip {
+    frame_width: u32,
+    frame_height: u32,
+    frame_count: u32,
+    vertical: bool,
+}
+
+fn detect_hit_fx_strip(width: u32, height: u32, frame_count: Option<u32>) -> Result<HitFxStrip, ConvertError> {
+    let vertical = height >= width;
+    let (length, thickness) = if vertical { (height, width) } else { (width, height) };
+
+    let frame_count = match frame_count {
+        Some(0) => return Err(ConvertError::Validation("Hit effect frame count must be positive".to_string())),
+        Some(count) => count,
+        None if thickness > 0 && length.is_multiple_of(thickness) => length / thickness,
+        None if length.is_multiple_of(PT_DEFAULT_HIT_FX_FRAMES) => PT_DEFAULT_HIT_FX_FRAMES,
+        None => return Err(ConvertError::Validation(format!(
+            "Cannot infer hit effect frames from a {}x{} strip, set hitFxFrames in the meta",
+            width, height
+        ))),
+    };
+
+    if !length.is_multiple_of(frame_count) {
+        return Err(ConvertError::Validation(format!(
+            "Hit effect strip of {}x{} cannot be split into {} frames",
+            width, height, frame_count
+        )));
+    }
+
+    let frame_length = length / frame_count;
+    let (frame_width, frame_height) = if vertical { (width, frame_length) } else { (frame_length, height) };
+
+    Ok(HitFxStrip {
+        frame_width,
+        frame_height,
+        frame_count,
+        vertical,
+    })
+}
+
+pub fn hit_fx_grid(frame_count: u32) -> (u32, u32) {
+    let columns = (1..=frame_count)
+        .filter(|columns| frame_count.is_multiple_of(*columns) && columns * columns <= frame_count)
+        .max()
+        .unwrap_or(1);
+    (columns, frame_count / columns)
+}
+
+fn decode_image(data: &[u8], img_type: &ImageResType) -> Result<DynamicImage, ConvertError> {
+    image::load_from_memory(data).map_err(|e| ConvertError::image(img_type, e))
+}
+
+/// Decoded pixels, kept at 8 bits per channel unless the source has more.
+enum SourcePixels {
+    Rgba8(RgbaImage),
+    Rgba16(Rgba16Image),
+}
+
+impl SourcePixels {
+    fn from_decoded(img: DynamicImage) -> SourcePixels {
+        let color = img.color();
+        if color.bytes_per_pixel() / color.channel_count() > 1 {
+            SourcePixels::Rgba16(img.into_rgba16())
+        } else {
+            SourcePixels::Rgba8(img.into_rgba8())
+        }
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            SourcePixels::Rgba8(img) => img.dimensions(),
+            SourcePixels::Rgba16(img) => img.dimensions(),
+        }
+    }
+
+    fn is_16bit(&self) -> bool {
+        matches!(self, SourcePixels::Rgba16(_))
+    }
+
+    fn into_rgba8(self) -> RgbaImage {
+        match self {
+            SourcePixels::Rgba8(img) => img,
+            SourcePixels::Rgba16(img) => DynamicImage::ImageRgba16(img).to_rgba8(),
+        }
+    }
+
+    fn into_rgba16(self) -> Rgba16Image {
+        match self {
+            SourcePixels::Rgba8(img) => DynamicImage::ImageRgba8(img).to_rgba16(),
+            SourcePixels::Rgba16(img) => img,
+        }
+    }
+
+    /// Encodes as an RGBA PNG, at 16 bits per channel only when `sixteen_bit` is set.
+    fn encode(self, sixteen_bit: bool, img_type: &ImageResType) -> Result<Vec<u8>, ConvertError> {
+        match self {
+            SourcePixels::Rgba16(img) if sixteen_bit => {
+                let mut output = Vec::new();
+                img.write_to(&mut Cursor::new(&mut output), ImageOutputFormat::Png)
+                    .map_err(|e| ConvertError::image(img_type, e))?;
+                Ok(output)
+            },
+            pixels => encode_png(&pixels.into_rgba8(), img_type),
+        }
+    }
+}
+
+/// A source image in sRGB with straight alpha.
+struct SourceImage {
+    pixels: SourcePixels,
+    format: ImageFormat,
+    /// The colour type as stored in the source.
+    color: ColorType,
+    has_profile: bool,
+}
+
+fn read_icc_profile(data: &[u8], format: ImageFormat) -> Option<Vec<u8>> {
+    match format {
+        ImageFormat::Png => PngDecoder::new(Cursor::new(data)).ok()?.icc_profile(),
+        ImageFormat::Jpeg => JpegDecoder::new(Cursor::new(data)).ok()?.icc_profile(),
+        _ => None,
+    }
+}
+
+/// Converts 8-bit RGBA pixels from the embedded profile to sRGB. Returns false, leaving
+/// the pixels alone, when the profile cannot be parsed or does not describe RGB data.
+fn convert_to_srgb(pixels: &mut RgbaImage, icc: &[u8]) -> bool {
+    let Some(input) = qcms::Profile::new_from_slice(icc, false) else {
+        return false;
+    };
+    let mut output = qcms::Profile::new_sRGB();
+    output.precache_output_transform();
+    let Some(transform) = qcms::Transform::new(&input, &output, qcms::DataType::RGBA8, qcms::Intent::default()) else {
+        return false;
+    };
+    transform.apply(pixels);
+    true
+}
+
+/// Divides the colour channels of interleaved RGBA samples by their alpha.
+fn unpremultiply<S>(samples: &mut [S], max: S)
+where
+    S: Copy + Into<u32> + TryFrom<u32>,
+{
+    let max: u32 = max.into();
+    for pixel in samples.chunks_exact_mut(4) {
+        let alpha: u32 = pixel[3].into();
+        if alpha == 0 {
+            continue;
+        }
+        for channel in &mut pixel[..3] {
+            if let Ok(value) = S::try_from(((*channel).into() * max / alpha).min(max)) {
+                *channel = value;
+            }
+        }
+    }
+}
+
+/// Decodes an image into sRGB with straight alpha, un-premultiplying it when
+/// `premultiplied` is set. Colour management works on 8 bits per channel, so 16-bit
+/// sources with an embedded profile lose their extra depth.
+fn load_image(data: &[u8], img_type: &ImageResType, premultiplied: bool) -> Result<SourceImage, ConvertError> {
+    let format = image::guess_format(data).map_err(|e| ConvertError::image(img_type, e))?;
+    let img = image::load_from_memory_with_format(data, format).map_err(|e| ConvertError::image(img_type, e))?;
+    let color = img.color();
+
+    let mut has_profile = false;
+    let mut pixels = match read_icc_profile(data, format) {
+        Some(icc) => {
+            let mut rgba = img.to_rgba8();
+            has_profile = convert_to_srgb(&mut rgba, &icc);
+            if has_profile {
+                SourcePixels::Rgba8(rgba)
+            } else {
+                SourcePixels::from_decoded(img)
+            }
+        },
+        None => SourcePixels::from_decoded(img),
+    };
+    if premultiplied {
+        match &mut pixels {
+            SourcePixels::Rgba8(img) => unpremultiply(img, u8::MAX),
+            SourcePixels::Rgba16(img) => unpremultiply(img, u16::MAX),
+        }
+    }
+
+    Ok(SourceImage {
+        pixels,
+        format,
+        color,
+        has_profile,
+    })
+}
+
+/// Copies `src` into `dst` with its top-left corner at (`x`, `y`), one row slice at a time.
+fn blit<P>(dst: &mut Buffer<P>, src: &Buffer<P>, x: u32, y: u32)
+where
+    P: Pixel,
+{
+    let channels = P::CHANNEL_COUNT as usize;
+    let row_len = src.width() as usize * channels;
+    if row_len == 0 {
+        return;
+    }
+    let dst_stride = dst.width() as usize * channels;
+    let offset = x as usize * channels;
+
+    for (row, src_row) in src.chunks_exact(row_len).enumerate() {
+        let start = (y as usize + row) * dst_stride + offset;
+        (**dst)[start..start + row_len].copy_from_slice(src_row);
+    }
+}
+
+/// Rearranges the frames of a strip into the grid, filling output rows in parallel. Every
+/// output row is made of one row slice from each frame in that grid row.
+fn lay_out_hit_fx<P>(img: &Buffer<P>, strip: &HitFxStrip) -> (Buffer<P>, (u32, u32))
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+{
+    let (frame_width, frame_height) = (strip.frame_width, strip.frame_height);
+    let (columns, rows) = hit_fx_grid(strip.frame_count);
+    let mut grid = Buffer::<P>::new(frame_width * columns, frame_height * rows);
+
+    let channels = P::CHANNEL_COUNT as usize;
+    let src_stride = img.width() as usize * channels;
+    let frame_row = frame_width as usize * channels;
+    let src = img.as_raw();
+
+    grid.par_chunks_mut((frame_row * columns as usize).max(1))
+        .enumerate()
+        .for_each(|(y, row)| {
+            let (grid_row, frame_y) = (y as u32 / frame_height, y as u32 % frame_height);
+            for column in 0..columns {
+                let i = grid_row * columns + column;
+                let (old_x, old_y) = if strip.vertical {
+                    (0, i * frame_height + frame_y)
+                } else {
+                    (i * frame_width, frame_y)
+                };
+                let start = old_y as usize * src_stride + old_x as usize * channels;
+                let dst_start = column as usize * frame_row;
+                row[dst_start..dst_start + frame_row].copy_from_slice(&src[start..start + frame_row]);
+            }
+        });
+
+    (grid, (columns, rows))
+}
+
+/// A composited image, with the sources that had no alpha channel.
+pub(crate) struct Composite {
+    pub(crate) png: Vec<u8>,
+    pub(crate) layout: (u32, u32),
+    pub(crate) opaque_sources: Vec<(ImageResType, ImageFormat)>,
+}
+
+fn opaque_sources<'a>(sources: impl IntoIterator<Item = (&'a ImageResType, &'a SourceImage)>) -> Vec<(ImageResType, ImageFormat)> {
+    sources
+        .into_iter()
+        .filter(|(_, source)| !source.color.has_alpha())
+        .map(|(img_type, source)| (img_type.clone(), source.format))
+        .collect()
+}
+
+pub fn hit_fx_convector(image_data: &[u8], frame_count: Option<u32>, options: &ImageOptions) -> Result<(Vec<u8>, (u32, u32)), ConvertError> {
+    convert_hit_fx(image_data, frame_count, options.premultiplied_alpha, options).map(|hit_fx| (hit_fx.png, hit_fx.layout))
+}
+
+pub(crate) fn convert_hit_fx(image_data: &[u8], frame_count: Option<u32>, premultiplied: bool, options: &ImageOptions) -> Result<Composite, ConvertError> {
+    let source = load_image(image_data, &ImageResType::HitFX, premultiplied)?;
+    let opaque_sources = opaque_sources([(&ImageResType::HitFX, &source)]);
+
+    let (width, height) = source.pixels.dimensions();
+    let strip = detect_hit_fx_strip(width, height, frame_count)?;
+    let (grid, layout) = match &source.pixels {
+        SourcePixels::Rgba8(img) => {
+            let (grid, layout) = lay_out_hit_fx(img, &strip);
+            (SourcePixels::Rgba8(grid), layout)
+        },
+        SourcePixels::Rgba16(img) => {
+            let (grid, layout) = lay_out_hit_fx(img, &strip);
+            (SourcePixels::Rgba16(grid), layout)
+        },
+    };
+
+    Ok(Composite {
+        png: grid.encode(options.preserve_16bit, &ImageResType::HitFX)?,
+        layout,
+        opaque_sources,
+    })
+}
+
+/// The in-memory part of [`hit_fx_convector`]: lays out an already decoded strip as a grid.
+pub fn hit_fx_grid_image(img: &RgbaImage, frame_count: Option<u32>) -> Result<(RgbaImage, (u32, u32)), ConvertError> {
+    let strip = detect_hit_fx_strip(img.width(), img.height(), frame_count)?;
+    Ok(lay_out_hit_fx(img, &strip))
+}
+
+pub fn combine_hold_images(holdend: &[u8], hold: &[u8], holdhead: &[u8], options: &ImageOptions) -> Result<(Vec<u8>, (u32, u32)), ConvertError> {
+    let piece = |img_type, data| HoldPiece {
+        img_type,
+        data,
+        premultiplied: options.premultiplied_alpha,
+    };
+    combine_hold_pieces(
+        [piece(ImageResType::HoldEnd, holdend), piece(ImageResType::Hold, hold), piece(ImageResType::HoldHead, holdhead)],
+        &ImageResType::CombinedHold,
+        options,
+    )
+    .map(|atlas| (atlas.png, atlas.layout))
+}
+
+/// One source of a hold atlas. Pieces may come from different places, so each says
+/// whether it has premultiplied alpha.
+pub(crate) struct HoldPiece<'a> {
+    pub(crate) img_type: ImageResType,
+    pub(crate) data: &'a [u8],
+    pub(crate) premultiplied: bool,
+}
+
+pub(crate) fn combine_hold_pieces(pieces: [HoldPiece; 3], atlas_type: &ImageResType, options: &ImageOptions) -> Result<Composite, ConvertError> {
+    let sources = [
+        load_image(pieces[0].data, &pieces[0].img_type, pieces[0].premultiplied)?,
+        load_image(pieces[1].data, &pieces[1].img_type, pieces[1].premultiplied)?,
+        load_image(pieces[2].data, &pieces[2].img_type, pieces[2].premultiplied)?,
+    ];
+    let opaque_sources = opaque_sources(pieces.iter().map(|piece| &piece.img_type).zip(&sources));
+    let layout = (sources[0].pixels.dimensions().1, sources[2].pixels.dimensions().1);
+
+    // 8-bit pieces are only widened when another piece needs 16 bits.
+    let combined = if sources.iter().any(|source| source.pixels.is_16bit()) {
+        SourcePixels::Rgba16(stack_hold_pieces(sources.map(|source| source.pixels.into_rgba16())))
+    } else {
+        SourcePixels::Rgba8(stack_hold_pieces(sources.map(|source| source.pixels.into_rgba8())))
+    };
+
+    Ok(Composite {
+        png: combined.encode(options.preserve_16bit, atlas_type)?,
+        layout,
+        opaque_sources,
+    })
+}
+
+/// Stacks the end, body and head of a hold from top to bottom, centred horizontally.
+fn stack_hold_pieces<P: Pixel>(pieces: [Buffer<P>; 3]) -> Buffer<P> {
+    let [end_img, hold_img, head_img] = pieces;
+    let width = end_img.width().max(hold_img.width()).max(head_img.width());
+    let height = end_img.height() + hold_img.height() + head_img.height();
+
+    let mut combined = Buffer::<P>::new(width, height);
+    blit(&mut combined, &end_img, (width - end_img.width()) / 2, 0);
+    blit(&mut combined, &hold_img, (width - hold_img.width()) / 2, end_img.height());
+    blit(&mut combined, &head_img, (width - head_img.width()) / 2, end_img.height() + hold_img.height());
+    combined
+}
+
+/// Decodes an image into 8-bit sRGB with straight alpha, for sprites that are drawn on.
+pub(crate) fn load_rgba8(data: &[u8], img_type: &ImageResType, premultiplied: bool) -> Result<RgbaImage, ConvertError> {
+    Ok(load_image(data, img_type, premultiplied)?.pixels.into_rgba8())
+}
+
+pub(crate) fn encode_png(img: &RgbaImage, img_type: &ImageResType) -> Result<Vec<u8>, ConvertError> {
+    let mut output = Vec::new();
+    let encoder = image::codecs::png::PngEncoder::new(&mut output);
+    encoder.write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ColorType::Rgba8
+    ).map_err(|e| ConvertError::image(img_type, e))?;
+    Ok(output)
+}
+
+pub(crate) struct NormalizedImage {
+    /// The re-encoded PNG, or `None` when the input already is a plain RGBA8 PNG.
+    pub(crate) content: Option<Vec<u8>>,
+    pub(crate) format: ImageFormat,
+    pub(crate) has_alpha: bool,
+}
+
+/// Detects the real format of an image from its content and re-encodes it as an sRGB
+/// PNG with straight alpha. Only the first frame of an animated image is kept.
+pub(crate) fn normalize_image(data: &[u8], img_type: &ImageResType, premultiplied: bool, options: &ImageOptions) -> Result<NormalizedImage, ConvertError> {
+    let source = load_image(data, img_type, premultiplied)?;
+    let has_alpha = source.color.has_alpha();
+    let is_plain_png = source.format == ImageFormat::Png
+        && source.color == ColorType::Rgba8
+        && !source.has_profile
+        && !premultiplied;
+
+    let content = if is_plain_png {
+        None
+    } else {
+        Some(source.pixels.encode(options.preserve_16bit, img_type)?)
+    };
+    Ok(NormalizedImage {
+        content,
+        format: source.format,
+        has_alpha,
+    })
+}
+
+pub fn split_hold_atlas(atlas: &[u8], hold_atlas: (u32, u32)) -> Result<HoldPiecePngs, ConvertError> {
+    split_hold_pieces(atlas, hold_atlas, &ImageResType::CombinedHold)
+}
+
+pub(crate) fn split_hold_pieces(atlas: &[u8], hold_atlas: (u32, u32), atlas_type: &ImageResType) -> Result<HoldPiecePngs, ConvertError> {
+    let img = decode_image(atlas, atlas_type)?.to_rgba8();
+    let (width, height) = img.dimensions();
+    let (end_height, head_height) = hold_atlas;
+
+    if end_height == 0 || head_height == 0 || end_height + head_height >= height {
+        return Err(ConvertError::Validation(format!(
+            "Hold atlas {:?} does not fit an image of height {}",
+            hold_atlas, height
+        )));
+    }
+    let body_height = height - end_height - head_height;
+
+    let end = image::imageops::crop_imm(&img, 0, 0, width, end_height).to_image();
+    let hold = image::imageops::crop_imm(&img, 0, end_height, width, body_height).to_image();
+    let head = image::imageops::crop_imm(&img, 0, end_height + body_height, width, head_height).to_image();
+
+    Ok((
+        encode_png(&end, atlas_type)?,
+        encode_png(&hold, atlas_type)?,
+        encode_png(&head, atlas_type)?,
+    ))
+}
+
+pub fn hit_fx_grid_to_strip(image_data: &[u8], hit_fx: (u32, u32)) -> Result<Vec<u8>, ConvertError> {
+    let img = decode_image(image_data, &ImageResType::HitFX)?.to_rgba8();
+    let (columns, rows) = hit_fx;
+
+    if columns == 0 || rows == 0 || img.width() < columns || img.height() < rows {
+        return Err(ConvertError::Validation(format!(
+            "Hit effect grid {:?} does not fit an image of {}x{}",
+            hit_fx, img.width(), img.height()
+        )));
+    }
+
+    let frame_width = img.width() / columns;
+    let frame_height = img.height() / rows;
+    let frame_count = columns * rows;
+
+    let mut strip = RgbaImage::new(frame_width, frame_height * frame_count);
+
+    for i in 0..frame_count {
+        let old_x = (i % columns) * frame_width;
+        let old_y = (i / columns) * frame_height;
+        let frame = image::imageops::crop_imm(&img, old_x, old_y, frame_width, frame_height);
+        strip.copy_from(&*frame, 0, i * frame_height)
+            .map_err(|e| ConvertError::image(&ImageResType::HitFX, e))?;
+    }
+
+    encode_png(&strip, &ImageResType::HitFX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayAlphaImage, GrayImage, Luma, LumaA, Rgb, RgbImage};
+
+    fn png(img: DynamicImage) -> Vec<u8> {
+        let mut output = Vec::new();
+        img.write_to(&mut Cursor::new(&mut output), ImageOutputFormat::Png).unwrap();
+        output
+    }
+
+    fn l8(height: u32, value: u8) -> Vec<u8> {
+        png(DynamicImage::ImageLuma8(GrayImage::from_pixel(2, height, Luma([value]))))
+    }
+
+    fn la8(height: u32, value: u8, alpha: u8) -> Vec<u8> {
+        png(DynamicImage::ImageLumaA8(GrayAlphaImage::from_pixel(2, height, LumaA([value, alpha]))))
+    }
+
+    fn rgb8(height: u32, pixel: [u8; 3]) -> Vec<u8> {
+        png(DynamicImage::ImageRgb8(RgbImage::from_pixel(2, height, Rgb(pixel))))
+    }
+
+    fn rgba8(height: u32, pixel: [u8; 4]) -> Vec<u8> {
+        png(DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, height, Rgba(pixel))))
+    }
+
+    fn rgba16(height: u32, pixel: [u16; 4]) -> Vec<u8> {
+        png(DynamicImage::ImageRgba16(Rgba16Image::from_pixel(2, height, Rgba(pixel))))
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for byte in bytes {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    /// Inserts an `iCCP` chunk after `IHDR`, storing the profile in an uncompressed zlib stream.
+    fn with_icc_profile(png: &[u8], icc: &[u8]) -> Vec<u8> {
+        let (mut a, mut b) = (1u32, 0u32);
+        for byte in icc {
+            a = (a + *byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        let length = icc.len() as u16;
+
+        let mut chunk = b"iCCP".to_vec();
+        chunk.extend_from_slice(b"test\0\0");
+        chunk.extend_from_slice(&[0x78, 0x01, 0x01]);
+        chunk.extend_from_slice(&length.to_le_bytes());
+        chunk.extend_from_slice(&(!length).to_le_bytes());
+        chunk.extend_from_slice(icc);
+        chunk.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+        // The signature and the IHDR chunk take the first 33 bytes.
+        let mut output = png[..33].to_vec();
+        output.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+        output.extend_from_slice(&chunk);
+        output.extend_from_slice(&crc32(&chunk).to_be_bytes());
+        output.extend_from_slice(&png[33..]);
+        output
+    }
+
+    /// A display profile with sRGB primaries and linear tone curves.
+    fn linear_rgb_profile() -> Vec<u8> {
+        let xyz = |[x, y, z]: [f64; 3]| {
+            let mut tag = b"XYZ \0\0\0\0".to_vec();
+            for value in [x, y, z] {
+                tag.extend_from_slice(&((value * 65536.0).round() as i32).to_be_bytes());
+            }
+            tag
+        };
+        let curve = b"curv\0\0\0\0\0\0\0\0".to_vec();
+        let tags = [
+            (b"wtpt", xyz([0.9642, 1.0, 0.8249])),
+            (b"rXYZ", xyz([0.4361, 0.2225, 0.0139])),
+            (b"gXYZ", xyz([0.3851, 0.7169, 0.0971])),
+            (b"bXYZ", xyz([0.1431, 0.0606, 0.7141])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ];
+
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        let mut data = Vec::new();
+        let data_start = 128 + 4 + 12 * tags.len();
+        for (signature, tag) in &tags {
+            table.extend_from_slice(*signature);
+            table.extend_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+            table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+            data.extend_from_slice(tag);
+        }
+
+        let mut profile = vec![0; 128];
+        profile[..4].copy_from_slice(&((data_start + data.len()) as u32).to_be_bytes());
+        profile[8..12].copy_from_slice(&0x0210_0000u32.to_be_bytes());
+        profile[12..16].copy_from_slice(b"mntr");
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        profile.extend_from_slice(&table);
+        profile.extend_from_slice(&data);
+        profile
+    }
+
+    fn decode(data: &[u8]) -> DynamicImage {
+        image::load_from_memory(data).unwrap()
+    }
+
+    #[test]
+    fn hold_pieces_of_any_colour_type_are_combined_as_rgba8() {
+        let options = ImageOptions::default();
+        let (atlas, layout) = combine_hold_images(&l8(3, 50), &la8(5, 80, 40), &rgb8(7, [10, 20, 30]), &options).unwrap();
+
+        assert_eq!(layout, (3, 7));
+        let atlas = decode(&atlas);
+        assert_eq!(atlas.color(), ColorType::Rgba8);
+        let atlas = atlas.to_rgba8();
+        assert_eq!(atlas.dimensions(), (2, 3 + 5 + 7));
+        assert_eq!(*atlas.get_pixel(0, 0), Rgba([50, 50, 50, 255]));
+        assert_eq!(*atlas.get_pixel(0, 3), Rgba([80, 80, 80, 40]));
+        assert_eq!(*atlas.get_pixel(0, 8), Rgba([10, 20, 30, 255]));
+    }
+
+    #[test]
+    fn sixteen_bit_hold_pieces_are_reduced_by_default() {
+        let holdend = rgba16(3, [0x1234, 0x8080, 0xffff, 0xffff]);
+        let (atlas, _) = combine_hold_images(&holdend, &l8(5, 50), &l8(7, 50), &ImageOptions::default()).unwrap();
+
+        let atlas = decode(&atlas);
+        assert_eq!(atlas.color(), ColorType::Rgba8);
+        assert_eq!(*atlas.to_rgba8().get_pixel(0, 0), Rgba([0x12, 0x80, 0xff, 0xff]));
+    }
+
+    #[test]
+    fn sixteen_bit_hold_pieces_can_be_preserved() {
+        let options = ImageOptions {
+            preserve_16bit: true,
+            ..ImageOptions::default()
+        };
+        let holdend = rgba16(3, [0x1234, 0x8080, 0xffff, 0xffff]);
+        let (atlas, _) = combine_hold_images(&holdend, &la8(5, 80, 40), &l8(7, 50), &options).unwrap();
+
+        let atlas = decode(&atlas);
+        assert_eq!(atlas.color(), ColorType::Rgba16);
+        let atlas = atlas.to_rgba16();
+        assert_eq!(*atlas.get_pixel(0, 0), Rgba([0x1234, 0x8080, 0xffff, 0xffff]));
+        // 8-bit pieces are widened exactly.
+        assert_eq!(*atlas.get_pixel(0, 3), Rgba([80 * 257, 80 * 257, 80 * 257, 40 * 257]));
+    }
+
+    #[test]
+    fn hit_fx_of_any_colour_type_is_laid_out_as_rgba8() {
+        let strips = [
+            (l8(8, 50), Rgba([50, 50, 50, 255])),
+            (la8(8, 80, 40), Rgba([80, 80, 80, 40])),
+            (rgb8(8, [10, 20, 30]), Rgba([10, 20, 30, 255])),
+            (rgba16(8, [0x1234, 0x8080, 0xffff, 0x8080]), Rgba([0x12, 0x80, 0xff, 0x80])),
+        ];
+
+        for (strip, pixel) in strips {
+            let (grid, layout) = hit_fx_convector(&strip, Some(4), &ImageOptions::default()).unwrap();
+            assert_eq!(layout, (2, 2));
+            let grid = decode(&grid);
+            assert_eq!(grid.color(), ColorType::Rgba8);
+            assert_eq!((grid.width(), grid.height()), (4, 4));
+            assert_eq!(*grid.to_rgba8().get_pixel(3, 3), pixel);
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_hit_fx_can_be_preserved() {
+        let options = ImageOptions {
+            preserve_16bit: true,
+            ..ImageOptions::default()
+        };
+        let strip = rgba16(8, [0x1234, 0x8080, 0xffff, 0x8080]);
+        let (grid, _) = hit_fx_convector(&strip, Some(4), &options).unwrap();
+
+        let grid = decode(&grid);
+        assert_eq!(grid.color(), ColorType::Rgba16);
+        assert_eq!(*grid.to_rgba16().get_pixel(3, 3), Rgba([0x1234, 0x8080, 0xffff, 0x8080]));
+    }
+
+    #[test]
+    fn premultiplied_sources_are_unpremultiplied() {
+        let options = ImageOptions {
+            premultiplied_alpha: true,
+            ..ImageOptions::default()
+        };
+        let piece = rgba8(3, [100, 100, 100, 128]);
+        let (atlas, _) = combine_hold_images(&piece, &rgba8(5, [0, 0, 0, 0]), &l8(7, 50), &options).unwrap();
+
+        let atlas = decode(&atlas).to_rgba8();
+        assert_eq!(*atlas.get_pixel(0, 0), Rgba([199, 199, 199, 128]));
+        assert_eq!(*atlas.get_pixel(0, 3), Rgba([0, 0, 0, 0]));
+        assert_eq!(*atlas.get_pixel(0, 8), Rgba([50, 50, 50, 255]));
+
+        let (grid, _) = hit_fx_convector(&rgba8(8, [100, 100, 100, 128]), Some(4), &options).unwrap();
+        assert_eq!(*decode(&grid).to_rgba8().get_pixel(0, 0), Rgba([199, 199, 199, 128]));
+    }
+
+    #[test]
+    fn icc_tagged_sources_are_converted_to_srgb() {
+        let tagged = with_icc_profile(&rgb8(8, [128, 128, 128]), &linear_rgb_profile());
+        let untagged = rgb8(8, [128, 128, 128]);
+        assert!(read_icc_profile(&tagged, ImageFormat::Png).is_some());
+
+        let (grid, _) = hit_fx_convector(&tagged, Some(4), &ImageOptions::default()).unwrap();
+        let grid = decode(&grid);
+        assert_eq!(grid.color(), ColorType::Rgba8);
+        // Linear mid grey is about 188 in sRGB.
+        let Rgba([r, g, b, a]) = *grid.to_rgba8().get_pixel(0, 0);
+        for channel in [r, g, b] {
+            assert!(channel.abs_diff(188) <= 2, "{}", channel);
+        }
+        assert_eq!(a, 255);
+
+        let (atlas, _) = combine_hold_images(&tagged, &untagged, &l8(7, 50), &ImageOptions::default()).unwrap();
+        let atlas = decode(&atlas).to_rgba8();
+        assert!(atlas.get_pixel(0, 0)[0].abs_diff(188) <= 2);
+        assert_eq!(*atlas.get_pixel(0, 8), Rgba([128, 128, 128, 255]));
+    }
+}
@@ -0,0 +1,222 @@
+use bytes::Bytes;
+use std::io::Cursor;
+use std::num::{NonZeroU32, NonZeroU8};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+use crate::dsp::{normalize, remix, resample, trim_leading_silence};
+use crate::error::ConvertError;
+use crate::meta::AudioResType;
+
+pub const DEFAULT_AUDIO_QUALITY: f32 = 6.0;
+const ENCODE_BLOCK_FRAMES: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    OggVorbis,
+    Ogg,
+    Mp3,
+    Wav,
+    Flac,
+}
+
+impl AudioFormat {
+    /// Detects the container from the first bytes of the file, ignoring its name.
+    pub fn sniff(data: &[u8]) -> Option<AudioFormat> {
+        if data.starts_with(b"OggS") {
+            // The first page of an Ogg Vorbis stream carries the "\x01vorbis" identification header.
+            let is_vorbis = data.get(28..35) == Some(b"\x01vorbis".as_slice());
+            return Some(if is_vorbis { AudioFormat::OggVorbis } else { AudioFormat::Ogg });
+        }
+        if data.starts_with(b"fLaC") {
+            return Some(AudioFormat::Flac);
+        }
+        if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WAVE".as_slice()) {
+            return Some(AudioFormat::Wav);
+        }
+        if data.starts_with(b"ID3") || (data.len() >= 2 && data[0] == 0xff && data[1] & 0xe0 == 0xe0) {
+            return Some(AudioFormat::Mp3);
+        }
+        None
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::OggVorbis | AudioFormat::Ogg => "ogg",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Target sample peak, in dBFS.
+    Peak(f32),
+    /// Target integrated loudness, in LUFS.
+    Lufs(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+}
+
+#[derive(Debug, Clone)]
+pub struct AudioOptions {
+    /// Vorbis quality on the `oggenc -q` scale, from -1 to 10.
+    pub quality: f32,
+    /// Cut leading samples quieter than this many dBFS.
+    pub trim_silence: Option<f32>,
+    pub normalize: Option<Normalization>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<ChannelLayout>,
+}
+
+impl AudioOptions {
+    fn processes(&self) -> bool {
+        self.trim_silence.is_some() || self.normalize.is_some() || self.sample_rate.is_some() || self.channels.is_some()
+    }
+}
+
+impl Default for AudioOptions {
+    fn default() -> Self {
+        AudioOptions {
+            quality: DEFAULT_AUDIO_QUALITY,
+            trim_silence: None,
+            normalize: None,
+            sample_rate: None,
+            channels: None,
+        }
+    }
+}
+
+/// Decoded audio, one buffer of samples per channel.
+pub(crate) struct PcmAudio {
+    pub(crate) sample_rate: u32,
+    pub(crate) channels: Vec<Vec<f32>>,
+}
+
+impl PcmAudio {
+    pub(crate) fn frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+}
+
+fn decode_audio(data: &[u8], format: AudioFormat) -> Result<PcmAudio, SymphoniaError> {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension(format.extension());
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())?;
+    let mut reader = probed.format;
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(SymphoniaError::Unsupported("no audio track"))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut channels: Vec<Vec<f32>> = Vec::new();
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        let spec = *decoded.spec();
+        sample_rate = Some(spec.rate);
+
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        samples.copy_planar_ref(decoded);
+        let channel_count = spec.channels.count();
+        let frames = samples.samples().len() / channel_count;
+        if channels.is_empty() {
+            channels.resize(channel_count, Vec::new());
+        }
+        for (index, channel) in channels.iter_mut().enumerate() {
+            channel.extend_from_slice(&samples.samples()[index * frames..(index + 1) * frames]);
+        }
+    }
+
+    Ok(PcmAudio {
+        sample_rate: sample_rate.ok_or(SymphoniaError::Unsupported("unknown sample rate"))?,
+        channels,
+    })
+}
+
+fn encode_vorbis(audio: &PcmAudio, quality: f32) -> Result<Vec<u8>, String> {
+    let sample_rate = NonZeroU32::new(audio.sample_rate).ok_or("sample rate is zero")?;
+    let channel_count = u8::try_from(audio.channels.len())
+        .ok()
+        .and_then(NonZeroU8::new)
+        .ok_or_else(|| format!("cannot encode {} channels", audio.channels.len()))?;
+
+    let mut output = Vec::new();
+    let mut builder = VorbisEncoderBuilder::new(sample_rate, channel_count, &mut output).map_err(|e| e.to_string())?;
+    builder.bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+        target_quality: (quality / 10.0).clamp(-0.1, 1.0),
+    });
+    let mut encoder = builder.build().map_err(|e| e.to_string())?;
+
+    let frames = audio.frames();
+    for start in (0..frames).step_by(ENCODE_BLOCK_FRAMES) {
+        let end = (start + ENCODE_BLOCK_FRAMES).min(frames);
+        let block: Vec<&[f32]> = audio.channels.iter().map(|channel| &channel[start..end]).collect();
+        encoder.encode_audio_block(&block).map_err(|e| e.to_string())?;
+    }
+    encoder.finish().map_err(|e| e.to_string())?;
+
+    Ok(output)
+}
+
+fn process_audio(audio: &mut PcmAudio, options: &AudioOptions) {
+    if let Some(layout) = options.channels {
+        remix(audio, layout);
+    }
+    if let Some(sample_rate) = options.sample_rate {
+        resample(audio, sample_rate);
+    }
+    if let Some(threshold) = options.trim_silence {
+        trim_leading_silence(audio, threshold);
+    }
+    if let Some(target) = options.normalize {
+        normalize(audio, target);
+    }
+}
+
+/// Turns a hit sound in any supported format into Ogg Vorbis, applying the optional
+/// audio pass. Ogg Vorbis files are kept as they are when there is nothing to apply.
+pub(crate) fn transcode_audio(data: &Bytes, audio_type: &AudioResType, options: &AudioOptions) -> Result<Bytes, ConvertError> {
+    let format = AudioFormat::sniff(data).ok_or_else(|| ConvertError::audio(audio_type, "unrecognised format"))?;
+    if format == AudioFormat::OggVorbis && !options.processes() {
+        return Ok(data.clone());
+    }
+
+    let mut audio = decode_audio(data, format)
+        .map_err(|e| ConvertError::audio(audio_type, format!("cannot decode {:?}: {}", format, e)))?;
+    process_audio(&mut audio, options);
+    let encoded = encode_vorbis(&audio, options.quality)
+        .map_err(|e| ConvertError::audio(audio_type, format!("cannot encode Ogg Vorbis: {}", e)))?;
+    Ok(Bytes::from(encoded))
+}
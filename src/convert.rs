@@ -0,0 +1,574 @@
+use bytes::Bytes;
+use image::ImageFormat;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::alias::ResAliases;
+use crate::atlas::{combine_hold_pieces, convert_hit_fx, hit_fx_grid_to_strip, normalize_image, split_hold_pieces, HoldPiece, ImageOptions, PRPR_DEFAULT_HIT_FX};
+use crate::audio::{transcode_audio, AudioOptions};
+use crate::error::ConvertError;
+use crate::fetch::{download_res, fetch_meta, Fetcher, DEFAULT_DOWNLOAD_JOBS, DEFAULT_RETRIES, DEFAULT_TIMEOUT};
+use crate::highlight::{generate_highlights, HighlightOptions};
+use crate::info::{generate_respack_info, InfoOverrides, ResPackInfo, INFO_FILENAME};
+use crate::local::{is_remote_input, open_local_respack, open_prpr_respack, read_local_res, LocalRespack, LOCAL_META_FILENAMES};
+use crate::meta::{get_filename, get_pt_res_key, res_name_parser, ImageResType, PTRespackMeta, ResType, PRPR_PASSTHROUGH_RES};
+use crate::report::ConvertReport;
+use crate::resolve::display_res_url;
+use crate::skin::{load_skin_res, missing_required_res, DefaultSkin};
+
+pub(crate) struct DownloadResult {
+    pub(crate) res_type: ResType,
+    pub(crate) content: Bytes,
+    /// False for resources filled in from a default skin or generated by the converter.
+    pub(crate) from_pack: bool,
+}
+
+impl DownloadResult {
+    /// Only the pack's own images are affected by `--premultiplied-alpha`.
+    fn premultiplied(&self, options: &ImageOptions) -> bool {
+        options.premultiplied_alpha && self.from_pack
+    }
+
+    fn hold_piece(&self, img_type: ImageResType, options: &ImageOptions) -> HoldPiece<'_> {
+        HoldPiece {
+            img_type,
+            data: &self.content,
+            premultiplied: self.premultiplied(options),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    pub info_overrides: InfoOverrides,
+    pub aliases: ResAliases,
+    pub jobs: usize,
+    pub quiet: bool,
+    pub timeout: Duration,
+    pub retries: u32,
+    /// Directory for the download cache. Off by default; the CLI uses `DEFAULT_CACHE_DIR`.
+    pub cache_dir: Option<PathBuf>,
+    pub offline: bool,
+    pub default_skin: Option<DefaultSkin>,
+    pub highlight: Option<HighlightOptions>,
+    pub image: ImageOptions,
+    pub audio: AudioOptions,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            info_overrides: InfoOverrides::default(),
+            aliases: ResAliases::default(),
+            jobs: DEFAULT_DOWNLOAD_JOBS,
+            quiet: false,
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            cache_dir: None,
+            offline: false,
+            default_skin: None,
+            highlight: None,
+            image: ImageOptions::default(),
+            audio: AudioOptions::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ProcessedRes {
+    pub(crate) files: BTreeMap<String, Bytes>,
+    pub(crate) hit_fx: Option<(u32, u32)>,
+    pub(crate) hold_atlas: Option<(u32, u32)>,
+    pub(crate) hold_atlas_mh: Option<(u32, u32)>,
+    pub(crate) warnings: Vec<String>,
+}
+
+impl ProcessedRes {
+    fn insert(&mut self, res_type: &ResType, content: Bytes) {
+        self.files.insert(get_filename(res_type).to_string(), content);
+    }
+
+    pub(crate) fn contains(&self, res_type: &ResType) -> bool {
+        self.files.contains_key(get_filename(res_type))
+    }
+
+    /// Hold pieces can end up in both atlases, so each image is only reported once.
+    fn warn_opaque(&mut self, img_type: &ImageResType, format: ImageFormat) {
+        let warning = format!(
+            "{:?} is a {:?} image without an alpha channel, its background will be opaque",
+            img_type, format
+        );
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+}
+
+fn process_res(downloads: Vec<DownloadResult>, meta: &PTRespackMeta, options: &ConvertOptions) -> Result<ProcessedRes, ConvertError> {
+    let mut processed = ProcessedRes::default();
+    let mut hold_components = HashMap::new();
+
+    for res in downloads {
+        match &res.res_type {
+            ResType::Image(ImageResType::HitFX) => {
+                let hit_fx = convert_hit_fx(&res.content, meta.hit_fx_frames, res.premultiplied(&options.image), &options.image)?;
+                for (img_type, format) in &hit_fx.opaque_sources {
+                    processed.warn_opaque(img_type, *format);
+                }
+                processed.insert(&res.res_type, Bytes::from(hit_fx.png));
+                processed.hit_fx = Some(hit_fx.layout);
+            },
+            ResType::Image(img_type) => {
+                match img_type {
+                    ImageResType::HoldEnd | ImageResType::Hold | ImageResType::HoldHead |
+                    ImageResType::HoldEndHL | ImageResType::HoldHL | ImageResType::HoldHeadHL => {
+                        hold_components.insert(img_type.clone(), res);
+                    },
+                    _ => {
+                        let normalized = normalize_image(&res.content, img_type, res.premultiplied(&options.image), &options.image)?;
+                        if !normalized.has_alpha {
+                            processed.warn_opaque(img_type, normalized.format);
+                        }
+                        processed.insert(&res.res_type, normalized.content.map_or(res.content, Bytes::from));
+                    },
+                }
+            },
+            ResType::Audio(audio_type) => {
+                let content = transcode_audio(&res.content, audio_type, &options.audio)?;
+                processed.insert(&res.res_type, content);
+            },
+        }
+    }
+
+    let hold_pieces = [ImageResType::HoldEnd, ImageResType::Hold, ImageResType::HoldHead];
+    let missing_pieces: Vec<&ImageResType> = hold_pieces
+        .iter()
+        .filter(|piece| !hold_components.contains_key(piece))
+        .collect();
+    if !missing_pieces.is_empty() && missing_pieces.len() < hold_pieces.len() {
+        processed.warnings.push(format!("Hold atlas skipped, missing {:?}", missing_pieces));
+    }
+
+    if let (Some(end), Some(hold), Some(head)) = (
+        hold_components.get(&ImageResType::HoldEnd),
+        hold_components.get(&ImageResType::Hold),
+        hold_components.get(&ImageResType::HoldHead)
+    ) {
+        let atlas = combine_hold_pieces(
+            [
+                end.hold_piece(ImageResType::HoldEnd, &options.image),
+                hold.hold_piece(ImageResType::Hold, &options.image),
+                head.hold_piece(ImageResType::HoldHead, &options.image),
+            ],
+            &ImageResType::CombinedHold,
+            &options.image,
+        )?;
+        for (img_type, format) in &atlas.opaque_sources {
+            processed.warn_opaque(img_type, *format);
+        }
+        processed.insert(&ResType::Image(ImageResType::CombinedHold), Bytes::from(atlas.png));
+        processed.hold_atlas = Some(atlas.layout);
+    }
+
+    let hl_piece = |hl: ImageResType, plain: ImageResType| {
+        match hold_components.get(&hl) {
+            Some(res) => Some(res.hold_piece(hl, &options.image)),
+            None => hold_components.get(&plain).map(|res| res.hold_piece(plain, &options.image)),
+        }
+    };
+    let has_hl_piece = [ImageResType::HoldEndHL, ImageResType::HoldHL, ImageResType::HoldHeadHL]
+        .iter()
+        .any(|hl| hold_components.contains_key(hl));
+
+    if let (true, Some(end), Some(hold), Some(head)) = (
+        has_hl_piece,
+        hl_piece(ImageResType::HoldEndHL, ImageResType::HoldEnd),
+        hl_piece(ImageResType::HoldHL, ImageResType::Hold),
+        hl_piece(ImageResType::HoldHeadHL, ImageResType::HoldHead)
+    ) {
+        let atlas = combine_hold_pieces(
+            [end, hold, head],
+            &ImageResType::CombinedHoldHL,
+            &options.image,
+        )?;
+        for (img_type, format) in &atlas.opaque_sources {
+            processed.warn_opaque(img_type, *format);
+        }
+        processed.insert(&ResType::Image(ImageResType::CombinedHoldHL), Bytes::from(atlas.png));
+        processed.hold_atlas_mh = Some(atlas.layout);
+    }
+
+    Ok(processed)
+}
+
+fn fill_from_skin(skin: &DefaultSkin, downloads: &mut Vec<DownloadResult>, meta: &mut PTRespackMeta, report: &mut ConvertReport, options: &ConvertOptions) -> Result<(), ConvertError> {
+    let missing = missing_required_res(downloads);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let skin_res = load_skin_res(skin, &missing, &options.aliases)?;
+    for res in skin_res.resources {
+        if res.res_type == ResType::Image(ImageResType::HitFX) {
+            meta.hit_fx_frames = skin_res.hit_fx_frames;
+        }
+        report.substituted.extend(get_pt_res_key(&res.res_type).map(str::to_string));
+        downloads.push(DownloadResult { from_pack: false, ..res });
+    }
+    report.substituted.sort();
+    Ok(())
+}
+
+fn convert_res(mut downloads: Vec<DownloadResult>, mut meta: PTRespackMeta, mut report: ConvertReport, options: &ConvertOptions) -> Result<ConvertedPack, ConvertError> {
+    if let Some(skin) = &options.default_skin {
+        fill_from_skin(skin, &mut downloads, &mut meta, &mut report, options)?;
+    }
+    if let Some(highlight) = &options.highlight {
+        let generated = generate_highlights(&mut downloads, highlight, options.image.premultiplied_alpha)?;
+        report.generated = generated
+            .iter()
+            .filter_map(get_pt_res_key)
+            .map(str::to_string)
+            .collect();
+    }
+
+    let name = meta.name.clone();
+    let mut processed = process_res(downloads, &meta, options)?;
+    report.warnings.append(&mut processed.warnings);
+    report.record_defaults(&processed.files);
+
+    let info = generate_respack_info(meta, &processed, &options.info_overrides);
+    let yaml = serde_yaml::to_string(&info)
+        .map_err(|e| ConvertError::Validation(format!("Failed to write {}: {}", INFO_FILENAME, e)))?;
+    processed.files.insert(INFO_FILENAME.to_string(), Bytes::from(yaml.into_bytes()));
+
+    Ok(ConvertedPack {
+        name,
+        info,
+        files: processed.files,
+        report,
+    })
+}
+
+/// A prpr respack converted in memory, keyed by file name inside the pack.
+#[derive(Debug)]
+pub struct ConvertedPack {
+    pub name: String,
+    pub info: ResPackInfo,
+    pub files: BTreeMap<String, Bytes>,
+    pub report: ConvertReport,
+}
+
+/// A PhiTogether pack produced by reverse conversion, with its `meta.json` among the files.
+#[derive(Debug)]
+pub struct ReversedPack {
+    pub name: String,
+    pub meta: PTRespackMeta,
+    pub files: BTreeMap<String, Bytes>,
+    pub warnings: Vec<String>,
+}
+
+fn reverse_res(pack: &mut LocalRespack, info: ResPackInfo) -> Result<ReversedPack, ConvertError> {
+    let name = format!("{}-pt", info.name);
+    let mut files = BTreeMap::new();
+    let mut warnings = Vec::new();
+    let mut res = BTreeMap::new();
+    let mut hit_fx_frames = None;
+
+    let mut add_res = |res_type: ResType, extension: &str, content: Bytes| {
+        if let Some(key) = get_pt_res_key(&res_type) {
+            let filename = format!("{}.{}", key, extension);
+            res.insert(key.to_string(), filename.clone());
+            files.insert(filename, content);
+        }
+    };
+
+    for res_type in PRPR_PASSTHROUGH_RES {
+        let prpr_filename = get_filename(&res_type);
+        if let Some(content) = pack.read_optional(prpr_filename)? {
+            let extension = prpr_filename.rsplit('.').next().unwrap_or_default();
+            add_res(res_type, extension, content);
+        }
+    }
+
+    if let Some(content) = pack.read_optional(get_filename(&ResType::Image(ImageResType::HitFX)))? {
+        let (columns, rows) = info.hit_fx.unwrap_or(PRPR_DEFAULT_HIT_FX);
+        let strip = hit_fx_grid_to_strip(&content, (columns, rows))?;
+        hit_fx_frames = Some(columns * rows);
+        add_res(ResType::Image(ImageResType::HitFX), "png", Bytes::from(strip));
+    }
+
+    if let Some(content) = pack.read_optional(get_filename(&ResType::Image(ImageResType::CombinedHold)))? {
+        match info.hold_atlas {
+            Some(hold_atlas) => {
+                let (end, hold, head) = split_hold_pieces(&content, hold_atlas, &ImageResType::CombinedHold)?;
+                add_res(ResType::Image(ImageResType::HoldEnd), "png", Bytes::from(end));
+                add_res(ResType::Image(ImageResType::Hold), "png", Bytes::from(hold));
+                add_res(ResType::Image(ImageResType::HoldHead), "png", Bytes::from(head));
+            },
+            None => warnings.push(format!("{} has no holdAtlas, skipping hold pieces", INFO_FILENAME)),
+        }
+    }
+
+    if let Some(content) = pack.read_optional(get_filename(&ResType::Image(ImageResType::CombinedHoldHL)))? {
+        match info.hold_atlas_mh {
+            Some(hold_atlas_mh) => {
+                let (end, hold, head) = split_hold_pieces(&content, hold_atlas_mh, &ImageResType::CombinedHoldHL)?;
+                add_res(ResType::Image(ImageResType::HoldEndHL), "png", Bytes::from(end));
+                add_res(ResType::Image(ImageResType::HoldHL), "png", Bytes::from(hold));
+                add_res(ResType::Image(ImageResType::HoldHeadHL), "png", Bytes::from(head));
+            },
+            None => warnings.push(format!("{} has no holdAtlasMH, skipping highlighted hold pieces", INFO_FILENAME)),
+        }
+    }
+
+    let meta = PTRespackMeta {
+        name: info.name,
+        author: info.author,
+        res,
+        hit_fx_frames,
+        description: Some(info.description).filter(|description| !description.is_empty()),
+        options: info.options,
+    };
+    let meta_json = serde_json::to_vec_pretty(&meta)
+        .map_err(|e| ConvertError::Validation(format!("Failed to write {}: {}", LOCAL_META_FILENAMES[0], e)))?;
+    files.insert(LOCAL_META_FILENAMES[0].to_string(), Bytes::from(meta_json));
+
+    Ok(ReversedPack { name, meta, files, warnings })
+}
+
+#[derive(Debug, Serialize)]
+pub struct InspectedRes {
+    pub key: String,
+    pub url: String,
+    #[serde(rename = "type")]
+    pub res_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InspectReport {
+    pub name: String,
+    pub author: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub resources: Vec<InspectedRes>,
+}
+
+/// Converts PhiTogether packs into prpr respacks (and back) without touching the output directory.
+/// Cloning is cheap, the options are shared.
+#[derive(Clone)]
+pub struct Converter {
+    options: Arc<ConvertOptions>,
+}
+
+impl Converter {
+    pub fn new(options: ConvertOptions) -> Converter {
+        Converter {
+            options: Arc::new(options),
+        }
+    }
+
+    /// Runs the CPU-heavy part of a conversion on Tokio's blocking pool, so converting
+    /// several packs at once does not stall each other's downloads.
+    async fn run_blocking<T, F>(&self, task: F) -> Result<T, ConvertError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Converter) -> Result<T, ConvertError> + Send + 'static,
+    {
+        let converter = self.clone();
+        match tokio::task::spawn_blocking(move || task(&converter)).await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(ConvertError::Io(io::Error::other(e))),
+        }
+    }
+
+    pub fn options(&self) -> &ConvertOptions {
+        &self.options
+    }
+
+    /// Converts a pack from a meta URL, a local meta JSON, a pack directory or a `.zip`.
+    pub async fn convert(&self, input: &str) -> Result<ConvertedPack, ConvertError> {
+        if is_remote_input(input) {
+            self.convert_url(input).await
+        } else {
+            let path = PathBuf::from(input);
+            self.run_blocking(move |converter| converter.convert_path(&path)).await
+        }
+    }
+
+    pub async fn convert_url(&self, url: &str) -> Result<ConvertedPack, ConvertError> {
+        let fetcher = Fetcher::new(&self.options)?;
+        let meta = fetch_meta(&fetcher, url).await?;
+        let res_urls = res_name_parser(&meta.res, &self.options.aliases);
+        let mut report = ConvertReport::new(&meta.res, &res_urls, &self.options.aliases);
+        let downloaded = download_res(&fetcher, url, res_urls, &self.options).await?;
+        report.warnings.extend(fetcher.take_warnings());
+        self.run_blocking(move |converter| convert_res(downloaded, meta, report, &converter.options)).await
+    }
+
+    /// Converts a local pack on the current thread.
+    pub fn convert_path(&self, path: &Path) -> Result<ConvertedPack, ConvertError> {
+        let (mut pack, meta) = open_local_respack(path)?;
+        let res_paths = res_name_parser(&meta.res, &self.options.aliases);
+        let report = ConvertReport::new(&meta.res, &res_paths, &self.options.aliases);
+        let loaded = read_local_res(&mut pack, res_paths)?;
+        convert_res(loaded, meta, report, &self.options)
+    }
+
+    /// Turns a prpr respack directory or `.zip` back into a PhiTogether pack.
+    pub fn reverse(&self, path: &Path) -> Result<ReversedPack, ConvertError> {
+        let (mut pack, info) = open_prpr_respack(path)?;
+        reverse_res(&mut pack, info)
+    }
+
+    pub async fn load_meta(&self, input: &str) -> Result<PTRespackMeta, ConvertError> {
+        if is_remote_input(input) {
+            fetch_meta(&Fetcher::new(&self.options)?, input).await
+        } else {
+            open_local_respack(Path::new(input)).map(|(_, meta)| meta)
+        }
+    }
+
+    pub async fn inspect(&self, input: &str) -> Result<InspectReport, ConvertError> {
+        let meta = self.load_meta(input).await?;
+        let recognised: HashMap<String, (ResType, Option<String>)> = res_name_parser(&meta.res, &self.options.aliases)
+            .into_iter()
+            .map(|(res_type, entry)| (entry.key, (res_type, entry.alias)))
+            .collect();
+
+        let resources = meta.res
+            .iter()
+            .map(|(key, url)| InspectedRes {
+                key: key.clone(),
+                url: display_res_url(url),
+                res_type: recognised.get(key).map(|(res_type, _)| format!("{:?}", res_type)),
+                alias: recognised.get(key).and_then(|(_, alias)| alias.clone()),
+            })
+            .collect();
+
+        Ok(InspectReport {
+            name: meta.name,
+            author: meta.author,
+            description: meta.description,
+            resources,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::encode_png;
+    use crate::info::ResPackOptions;
+    use image::{Rgba, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Bytes {
+        let img = RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]));
+        Bytes::from(encode_png(&img, &ImageResType::Tap).unwrap())
+    }
+
+    fn image_res(img_type: ImageResType, width: u32, height: u32) -> DownloadResult {
+        DownloadResult {
+            res_type: ResType::Image(img_type),
+            content: png(width, height),
+            from_pack: true,
+        }
+    }
+
+    fn meta(hit_fx_frames: Option<u32>) -> PTRespackMeta {
+        PTRespackMeta {
+            name: "test".to_string(),
+            author: "tester".to_string(),
+            res: BTreeMap::new(),
+            hit_fx_frames,
+            description: None,
+            options: ResPackOptions::default(),
+        }
+    }
+
+    fn process(downloads: Vec<DownloadResult>, meta: PTRespackMeta) -> (ProcessedRes, ResPackInfo) {
+        let processed = process_res(downloads, &meta, &ConvertOptions::default()).unwrap();
+        let info = generate_respack_info(meta, &processed, &InfoOverrides::default());
+        (processed, info)
+    }
+
+    #[test]
+    fn hit_fx_is_emitted_with_hit_fx_png() {
+        let (processed, info) = process(vec![image_res(ImageResType::HitFX, 8, 8 * 6)], meta(Some(6)));
+
+        assert!(processed.files.contains_key("hit_fx.png"));
+        assert_eq!(info.hit_fx, Some((2, 3)));
+    }
+
+    #[test]
+    fn hold_atlases_are_omitted_when_not_written() {
+        let downloads = vec![
+            image_res(ImageResType::HoldEnd, 8, 3),
+            image_res(ImageResType::Hold, 8, 5),
+        ];
+        let (processed, info) = process(downloads, meta(None));
+
+        assert!(!processed.files.contains_key("hold.png"));
+        assert!(!processed.files.contains_key("hold_mh.png"));
+        assert_eq!(info.hold_atlas, None);
+        assert_eq!(info.hold_atlas_mh, None);
+        assert_eq!(info.hit_fx, None);
+    }
+
+    #[test]
+    fn hold_atlases_match_the_pieces_used() {
+        let downloads = vec![
+            image_res(ImageResType::HoldEnd, 8, 3),
+            image_res(ImageResType::Hold, 8, 5),
+            image_res(ImageResType::HoldHead, 8, 7),
+            image_res(ImageResType::HoldEndHL, 10, 4),
+        ];
+        let (processed, info) = process(downloads, meta(None));
+
+        assert!(processed.files.contains_key("hold.png"));
+        assert!(processed.files.contains_key("hold_mh.png"));
+        assert_eq!(info.hold_atlas, Some((3, 7)));
+        // The highlighted end piece is used, the head falls back to the plain one.
+        assert_eq!(info.hold_atlas_mh, Some((4, 7)));
+
+        let atlas = image::load_from_memory(&processed.files["hold_mh.png"]).unwrap();
+        assert_eq!(atlas.height(), 4 + 5 + 7);
+    }
+
+    #[test]
+    fn premultiplied_alpha_only_applies_to_pack_images() {
+        let img = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 128]));
+        let content = Bytes::from(encode_png(&img, &ImageResType::Tap).unwrap());
+        let downloads = vec![
+            DownloadResult {
+                res_type: ResType::Image(ImageResType::Tap),
+                content: content.clone(),
+                from_pack: true,
+            },
+            DownloadResult {
+                res_type: ResType::Image(ImageResType::Drag),
+                content,
+                from_pack: false,
+            },
+        ];
+        let options = ConvertOptions {
+            image: ImageOptions {
+                premultiplied_alpha: true,
+                ..ImageOptions::default()
+            },
+            ..ConvertOptions::default()
+        };
+        let processed = process_res(downloads, &meta(None), &options).unwrap();
+
+        let pixel = |file: &str| *image::load_from_memory(&processed.files[file]).unwrap().to_rgba8().get_pixel(0, 0);
+        assert_eq!(pixel("click.png"), Rgba([199, 199, 199, 128]));
+        assert_eq!(pixel("drag.png"), Rgba([100, 100, 100, 128]));
+    }
+}
@@ -0,0 +1,297 @@
+use std::f64::consts::PI;
+
+use crate::audio::{ChannelLayout, Normalization, PcmAudio};
+
+/// Zero crossings of the resampling kernel on each side of a sample.
+const RESAMPLE_TAPS: f64 = 16.0;
+const LOUDNESS_BLOCK_SECONDS: f64 = 0.4;
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = 10.0;
+
+pub(crate) fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn peak(audio: &PcmAudio) -> f32 {
+    audio
+        .channels
+        .iter()
+        .flatten()
+        .fold(0.0, |peak, sample| peak.max(sample.abs()))
+}
+
+/// Downmixes to mono by averaging, duplicates mono to stereo, and keeps the front pair
+/// of anything wider.
+pub(crate) fn remix(audio: &mut PcmAudio, layout: ChannelLayout) {
+    let frames = audio.frames();
+    match layout {
+        ChannelLayout::Mono if audio.channels.len() > 1 => {
+            let count = audio.channels.len() as f32;
+            let mixed = (0..frames)
+                .map(|i| audio.channels.iter().map(|channel| channel[i]).sum::<f32>() / count)
+                .collect();
+            audio.channels = vec![mixed];
+        },
+        ChannelLayout::Stereo if audio.channels.len() == 1 => {
+            let mono = audio.channels[0].clone();
+            audio.channels.push(mono);
+        },
+        ChannelLayout::Stereo if audio.channels.len() > 2 => audio.channels.truncate(2),
+        _ => {},
+    }
+}
+
+fn windowed_sinc(x: f64, cutoff: f64, half_width: f64) -> f64 {
+    let window = 0.5 + 0.5 * (PI * x / half_width).cos();
+    let arg = PI * x * cutoff;
+    let sinc = if arg.abs() < 1e-9 { 1.0 } else { arg.sin() / arg };
+    cutoff * sinc * window
+}
+
+/// Band-limited resampling with a Hann-windowed sinc kernel. When downsampling the
+/// kernel is widened so it also acts as the anti-aliasing filter.
+pub(crate) fn resample(audio: &mut PcmAudio, sample_rate: u32) {
+    let frames = audio.frames();
+    if audio.sample_rate == sample_rate || frames == 0 {
+        audio.sample_rate = sample_rate;
+        return;
+    }
+
+    let ratio = sample_rate as f64 / audio.sample_rate as f64;
+    let cutoff = ratio.min(1.0);
+    let half_width = RESAMPLE_TAPS / cutoff;
+    let out_frames = (frames as f64 * ratio).round() as usize;
+
+    for channel in &mut audio.channels {
+        let input = std::mem::take(channel);
+        *channel = (0..out_frames)
+            .map(|n| {
+                let t = n as f64 / ratio;
+                let start = (t - half_width).ceil().max(0.0) as usize;
+                let end = ((t + half_width).floor() as usize).min(frames - 1);
+                (start..=end)
+                    .map(|k| input[k] as f64 * windowed_sinc(t - k as f64, cutoff, half_width))
+                    .sum::<f64>() as f32
+            })
+            .collect();
+    }
+    audio.sample_rate = sample_rate;
+}
+
+/// Cuts everything before the first sample at or above `threshold_db` dBFS. Sounds that
+/// never reach the threshold are left alone.
+pub(crate) fn trim_leading_silence(audio: &mut PcmAudio, threshold_db: f32) {
+    let threshold = db_to_gain(threshold_db);
+    let start = (0..audio.frames()).find(|&i| audio.channels.iter().any(|channel| channel[i].abs() >= threshold));
+    if let Some(start) = start.filter(|&start| start > 0) {
+        for channel in &mut audio.channels {
+            channel.drain(..start);
+        }
+    }
+}
+
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+}
+
+impl Biquad {
+    /// The first K-weighting stage of ITU-R BS.1770, a high shelf modelling the head.
+    /// The parameters reproduce the coefficients the standard gives for 48 kHz.
+    fn high_shelf(sample_rate: f64) -> Biquad {
+        let (gain_db, q, frequency) = (3.999_843_853_973_347, 0.707_175_236_955_419_6, 1_681.974_450_955_533);
+        let k = (PI * frequency / sample_rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+
+        Biquad::normalized(
+            [vh + vb * k / q + k * k, 2.0 * (k * k - vh), vh - vb * k / q + k * k],
+            [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    /// The second K-weighting stage, a high pass around 38 Hz.
+    fn high_pass(sample_rate: f64) -> Biquad {
+        let (q, frequency) = (0.500_327_037_323_877_3, 38.135_470_876_024_44);
+        let k = (PI * frequency / sample_rate).tan();
+
+        Biquad::normalized(
+            [1.0, -2.0, 1.0],
+            [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    fn normalized(b: [f64; 3], a: [f64; 3]) -> Biquad {
+        Biquad {
+            b: b.map(|coefficient| coefficient / a[0]),
+            a: a.map(|coefficient| coefficient / a[0]),
+        }
+    }
+
+    fn apply(&self, input: &[f64]) -> Vec<f64> {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        input
+            .iter()
+            .map(|&x| {
+                let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[1] * y1 - self.a[2] * y2;
+                (x2, x1, y2, y1) = (x1, x, y1, y);
+                y
+            })
+            .collect()
+    }
+}
+
+/// Gated integrated loudness in LUFS, following ITU-R BS.1770 with every channel
+/// weighted equally. Sounds shorter than one 400 ms block are measured as a single block.
+pub(crate) fn integrated_loudness(audio: &PcmAudio) -> Option<f32> {
+    let frames = audio.frames();
+    if frames == 0 {
+        return None;
+    }
+
+    let sample_rate = audio.sample_rate as f64;
+    let (shelf, high_pass) = (Biquad::high_shelf(sample_rate), Biquad::high_pass(sample_rate));
+    let weighted: Vec<Vec<f64>> = audio
+        .channels
+        .iter()
+        .map(|channel| {
+            let samples: Vec<f64> = channel.iter().map(|&sample| sample as f64).collect();
+            high_pass.apply(&shelf.apply(&samples))
+        })
+        .collect();
+
+    let block = ((sample_rate * LOUDNESS_BLOCK_SECONDS) as usize).clamp(1, frames);
+    let hop = (block / 4).max(1);
+    let powers: Vec<f64> = (0..=frames - block)
+        .step_by(hop)
+        .map(|start| {
+            weighted
+                .iter()
+                .map(|channel| channel[start..start + block].iter().map(|s| s * s).sum::<f64>() / block as f64)
+                .sum()
+        })
+        .collect();
+
+    let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+    let mean = |powers: &[f64]| powers.iter().sum::<f64>() / powers.len() as f64;
+
+    let above_absolute: Vec<f64> = powers.into_iter().filter(|&power| loudness(power) > ABSOLUTE_GATE_LUFS).collect();
+    if above_absolute.is_empty() {
+        return None;
+    }
+    let relative_gate = loudness(mean(&above_absolute)) - RELATIVE_GATE_LU;
+    let gated: Vec<f64> = above_absolute.into_iter().filter(|&power| loudness(power) > relative_gate).collect();
+
+    Some(loudness(mean(&gated)) as f32)
+}
+
+/// Scales the sound to the target peak or loudness. Loudness normalization never raises
+/// the peak above full scale, so a loud target may be missed rather than clipped.
+pub(crate) fn normalize(audio: &mut PcmAudio, target: Normalization) {
+    let peak = peak(audio);
+    if peak <= 0.0 {
+        return;
+    }
+
+    let gain = match target {
+        Normalization::Peak(peak_db) => db_to_gain(peak_db) / peak,
+        Normalization::Lufs(lufs) => match integrated_loudness(audio) {
+            Some(measured) => db_to_gain(lufs - measured).min(1.0 / peak),
+            None => return,
+        },
+    };
+    for sample in audio.channels.iter_mut().flatten() {
+        *sample *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(sample_rate: u32, channels: Vec<Vec<f32>>) -> PcmAudio {
+        PcmAudio { sample_rate, channels }
+    }
+
+    fn sine(sample_rate: u32, frequency: f64, seconds: f64) -> Vec<f32> {
+        (0..(sample_rate as f64 * seconds) as usize)
+            .map(|i| (2.0 * PI * frequency * i as f64 / sample_rate as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn full_scale_tone_measures_minus_three_lufs() {
+        // BS.1770 calibrates a 0 dBFS 997 Hz sine in one channel to -3.01 LKFS.
+        let tone = audio(48000, vec![sine(48000, 997.0, 3.0)]);
+        let loudness = integrated_loudness(&tone).unwrap();
+        assert!((loudness + 3.01).abs() < 0.05, "{}", loudness);
+    }
+
+    #[test]
+    fn silence_has_no_loudness() {
+        assert_eq!(integrated_loudness(&audio(48000, vec![vec![0.0; 48000]])), None);
+    }
+
+    #[test]
+    fn resampling_scales_the_length() {
+        let mut tone = audio(44100, vec![sine(44100, 440.0, 1.0), sine(44100, 440.0, 1.0)]);
+        resample(&mut tone, 48000);
+        assert_eq!(tone.sample_rate, 48000);
+        assert_eq!(tone.channels.iter().map(Vec::len).collect::<Vec<_>>(), [48000, 48000]);
+
+        resample(&mut tone, 22050);
+        assert_eq!(tone.frames(), 22050);
+    }
+
+    #[test]
+    fn trimming_starts_at_the_first_loud_sample() {
+        let mut sound = audio(48000, vec![vec![0.0, 0.001, 0.05, 0.5, 0.2], vec![0.0, 0.0, 0.0, 0.0, 0.0]]);
+        trim_leading_silence(&mut sound, -20.0);
+        assert_eq!(sound.channels, [vec![0.5, 0.2], vec![0.0, 0.0]]);
+    }
+
+    #[test]
+    fn quiet_sounds_are_not_trimmed() {
+        let mut sound = audio(48000, vec![vec![0.0, 0.001, 0.05]]);
+        trim_leading_silence(&mut sound, -20.0);
+        assert_eq!(sound.frames(), 3);
+    }
+
+    #[test]
+    fn mono_and_stereo_round_trip() {
+        let mut sound = audio(48000, vec![vec![0.1, -0.2, 0.3]]);
+        remix(&mut sound, ChannelLayout::Stereo);
+        assert_eq!(sound.channels, [vec![0.1, -0.2, 0.3], vec![0.1, -0.2, 0.3]]);
+
+        remix(&mut sound, ChannelLayout::Mono);
+        assert_eq!(sound.channels, [vec![0.1, -0.2, 0.3]]);
+    }
+
+    #[test]
+    fn stereo_is_downmixed_by_averaging() {
+        let mut sound = audio(48000, vec![vec![0.5, 0.0], vec![0.0, -0.5]]);
+        remix(&mut sound, ChannelLayout::Mono);
+        assert_eq!(sound.channels, [vec![0.25, -0.25]]);
+    }
+
+    #[test]
+    fn peak_normalization_scales_to_the_target() {
+        let mut sound = audio(48000, vec![vec![0.25, -0.125]]);
+        normalize(&mut sound, Normalization::Peak(-6.0));
+        let target = db_to_gain(-6.0);
+        assert!((sound.channels[0][0] - target).abs() < 1e-6);
+        assert!((sound.channels[0][1] + target / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn loudness_normalization_does_not_clip() {
+        let mut tone = audio(48000, vec![sine(48000, 997.0, 3.0)]);
+        normalize(&mut tone, Normalization::Lufs(0.0));
+        assert!(peak(&tone) <= 1.0 + 1e-6);
+
+        let mut tone = audio(48000, vec![sine(48000, 997.0, 3.0)]);
+        normalize(&mut tone, Normalization::Lufs(-23.0));
+        let loudness = integrated_loudness(&tone).unwrap();
+        assert!((loudness + 23.0).abs() < 0.05, "{}", loudness);
+    }
+}
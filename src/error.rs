@@ -0,0 +1,116 @@
+use std::fmt;
+use std::io;
+
+use crate::meta::{AudioResType, ImageResType, ResType};
+
+pub(crate) type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum ConvertError {
+    /// The pack's meta (PhiTogether meta JSON or prpr `info.yml`) could not be fetched, read or parsed.
+    MetaFetch {
+        location: String,
+        source: BoxError,
+    },
+    /// A resource could not be downloaded, or read from a local pack.
+    Download {
+        key: String,
+        url: String,
+        source: BoxError,
+    },
+    /// An image resource could not be decoded, or the converted image could not be encoded.
+    ImageDecode {
+        res_type: ResType,
+        source: image::ImageError,
+    },
+    /// An audio resource could not be processed.
+    Audio {
+        res_type: ResType,
+        message: String,
+    },
+    Io(io::Error),
+    /// The input is well-formed but cannot be converted as given.
+    Validation(String),
+}
+
+impl ConvertError {
+    pub(crate) fn meta(location: impl fmt::Display, source: impl Into<BoxError>) -> ConvertError {
+        ConvertError::MetaFetch {
+            location: location.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn download(key: &str, url: &str, source: impl Into<BoxError>) -> ConvertError {
+        ConvertError::Download {
+            key: key.to_string(),
+            url: url.to_string(),
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn image(img_type: &ImageResType, source: image::ImageError) -> ConvertError {
+        ConvertError::ImageDecode {
+            res_type: ResType::Image(img_type.clone()),
+            source,
+        }
+    }
+
+    pub(crate) fn audio(audio_type: &AudioResType, message: impl fmt::Display) -> ConvertError {
+        ConvertError::Audio {
+            res_type: ResType::Audio(audio_type.clone()),
+            message: message.to_string(),
+        }
+    }
+
+    /// A short, stable name for the variant, used in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConvertError::MetaFetch { .. } => "meta",
+            ConvertError::Download { .. } => "download",
+            ConvertError::ImageDecode { .. } => "image",
+            ConvertError::Audio { .. } => "audio",
+            ConvertError::Io(_) => "io",
+            ConvertError::Validation(_) => "validation",
+        }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::MetaFetch { location, source } => write!(f, "Failed to load meta from {}: {}", location, source),
+            ConvertError::Download { key, url, source } => write!(f, "Failed to download {} from {}: {}", key, url, source),
+            ConvertError::ImageDecode { res_type, source } => write!(f, "Failed to process image {:?}: {}", res_type, source),
+            ConvertError::Audio { res_type, message } => write!(f, "Failed to process audio {:?}: {}", res_type, message),
+            ConvertError::Io(e) => write!(f, "{}", e),
+            ConvertError::Validation(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConvertError::MetaFetch { source, .. } | ConvertError::Download { source, .. } => Some(source.as_ref()),
+            ConvertError::ImageDecode { source, .. } => Some(source),
+            ConvertError::Io(e) => Some(e),
+            ConvertError::Audio { .. } | ConvertError::Validation(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConvertError {
+    fn from(e: io::Error) -> Self {
+        ConvertError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for ConvertError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => ConvertError::Io(e),
+            e => ConvertError::Validation(format!("Invalid zip archive: {}", e)),
+        }
+    }
+}
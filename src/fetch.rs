@@ -0,0 +1,273 @@
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::convert::{ConvertOptions, DownloadResult};
+use crate::error::{BoxError, ConvertError};
+use crate::meta::{PTRespackMeta, ResEntry, ResType};
+use crate::progress::Progress;
+use crate::resolve::{decode_data_uri, display_res_url, is_data_uri, resolve_res_url};
+
+pub const DEFAULT_DOWNLOAD_JOBS: usize = 4;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_CACHE_DIR: &str = "cache";
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+fn is_transient_error(error: &Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error()
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || status == reqwest::StatusCode::REQUEST_TIMEOUT,
+        None => error.is_timeout() || error.is_connect() || error.is_request() || error.is_body(),
+    }
+}
+
+async fn with_retries<T, F, Fut>(retries: u32, mut request: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match request().await {
+            Err(e) if attempt < retries && is_transient_error(&e) => {
+                let backoff = 2u32.checked_pow(attempt).unwrap_or(u32::MAX);
+                let delay = RETRY_BASE_DELAY.saturating_mul(backoff).min(RETRY_MAX_DELAY);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+pub(crate) async fn fetch_meta(fetcher: &Fetcher, url: &str) -> Result<PTRespackMeta, ConvertError> {
+    let data = fetcher
+        .fetch(url, "meta", None)
+        .await
+        .map_err(|e| ConvertError::meta(url, e))?;
+
+    serde_json::from_slice(&data)
+        .map_err(|e| ConvertError::meta(url, e))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheEntryMeta {
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+struct CachedResponse {
+    meta: CacheEntryMeta,
+    body: Bytes,
+}
+
+struct DownloadCache {
+    dir: PathBuf,
+}
+
+impl DownloadCache {
+    fn entry_paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+        (self.dir.join(format!("{}.json", hash)), self.dir.join(format!("{}.bin", hash)))
+    }
+
+    fn load(&self, url: &str) -> Option<CachedResponse> {
+        let (meta_path, body_path) = self.entry_paths(url);
+        let meta: CacheEntryMeta = serde_json::from_slice(&fs::read(meta_path).ok()?).ok()?;
+        if meta.url != url {
+            return None;
+        }
+
+        let body = Bytes::from(fs::read(body_path).ok()?);
+        Some(CachedResponse { meta, body })
+    }
+
+    fn store(&self, meta: &CacheEntryMeta, body: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let (meta_path, body_path) = self.entry_paths(&meta.url);
+
+        let body_tmp = body_path.with_extension("bin.tmp");
+        fs::write(&body_tmp, body)?;
+        fs::rename(&body_tmp, &body_path)?;
+        fs::write(meta_path, serde_json::to_vec_pretty(meta)?)?;
+        Ok(())
+    }
+}
+
+enum Fetched {
+    NotModified,
+    Modified {
+        content: Vec<u8>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+    },
+}
+
+pub(crate) struct Fetcher {
+    client: reqwest::Client,
+    cache: Option<DownloadCache>,
+    offline: bool,
+    retries: u32,
+    /// Problems that did not stop the download, such as a cache that cannot be written.
+    warnings: Mutex<Vec<String>>,
+}
+
+impl Fetcher {
+    pub(crate) fn new(options: &ConvertOptions) -> Result<Fetcher, ConvertError> {
+        if options.offline && options.cache_dir.is_none() {
+            return Err(ConvertError::Validation("Offline mode needs the download cache".to_string()));
+        }
+
+        let client = reqwest::Client::builder()
+            .connect_timeout(options.timeout)
+            .timeout(options.timeout)
+            .build()
+            .map_err(std::io::Error::other)?;
+
+        Ok(Fetcher {
+            client,
+            cache: options.cache_dir.clone().map(|dir| DownloadCache { dir }),
+            offline: options.offline,
+            retries: options.retries,
+            warnings: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub(crate) fn take_warnings(&self) -> Vec<String> {
+        std::mem::take(&mut self.warnings.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    async fn fetch_attempt(&self, url: &str, key: &str, cached: Option<&CachedResponse>, progress: Option<&Progress>) -> Result<Fetched, Error> {
+        let mut request = self.client.get(url);
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.meta.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.meta.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let mut response = request.send().await?.error_for_status()?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+
+        let header = |name: reqwest::header::HeaderName| {
+            response.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header(reqwest::header::ETAG);
+        let last_modified = header(reqwest::header::LAST_MODIFIED);
+
+        let file_total = response.content_length();
+        let mut content = Vec::with_capacity(file_total.unwrap_or(0) as usize);
+
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    content.extend_from_slice(&chunk);
+                    if let Some(progress) = progress {
+                        progress.update(key, content.len() as u64, file_total, chunk.len() as u64);
+                    }
+                },
+                Ok(None) => return Ok(Fetched::Modified { content, etag, last_modified }),
+                Err(e) => {
+                    if let Some(progress) = progress {
+                        progress.discard(content.len() as u64);
+                    }
+                    return Err(e);
+                },
+            }
+        }
+    }
+
+    pub(crate) async fn fetch(&self, url: &str, key: &str, progress: Option<&Progress>) -> Result<Bytes, BoxError> {
+        let cached = self.cache.as_ref().and_then(|cache| cache.load(url));
+
+        let content = if self.offline {
+            cached
+                .ok_or("Not in the download cache")?
+                .body
+        } else {
+            let cached_ref = cached.as_ref();
+            let fetched = with_retries(self.retries, || self.fetch_attempt(url, key, cached_ref, progress)).await?;
+
+            match (fetched, cached) {
+                (Fetched::NotModified, Some(cached)) => cached.body,
+                (Fetched::NotModified, None) => {
+                    return Err("Unexpected 304 Not Modified".into());
+                },
+                (Fetched::Modified { content, etag, last_modified }, _) => {
+                    if let Some(cache) = &self.cache {
+                        let meta = CacheEntryMeta {
+                            url: url.to_string(),
+                            etag,
+                            last_modified,
+                        };
+                        if let Err(e) = cache.store(&meta, &content) {
+                            let warning = format!("Failed to cache {}: {}", display_res_url(url), e);
+                            self.warnings.lock().unwrap_or_else(PoisonError::into_inner).push(warning);
+                        }
+                    }
+                    Bytes::from(content)
+                },
+            }
+        };
+
+        if let Some(progress) = progress {
+            progress.finish_file(key, content.len() as u64);
+        }
+        Ok(content)
+    }
+}
+
+async fn fetch_res(fetcher: &Fetcher, base_url: &str, entry: &ResEntry, progress: &Progress) -> Result<Bytes, BoxError> {
+    if is_data_uri(&entry.url) {
+        let content = decode_data_uri(&entry.url)?;
+        progress.finish_file(&entry.key, content.len() as u64);
+        return Ok(content);
+    }
+
+    let url = resolve_res_url(base_url, &entry.url)?;
+    fetcher.fetch(&url, &entry.key, Some(progress)).await
+}
+
+pub(crate) async fn download_res(fetcher: &Fetcher, base_url: &str, res_urls: HashMap<ResType, ResEntry>, options: &ConvertOptions) -> Result<Vec<DownloadResult>, ConvertError> {
+    let progress = Progress::new(res_urls.len(), options.quiet);
+    let progress = &progress;
+
+    let downloaded = futures::stream::iter(res_urls)
+        .map(|(res_type, entry)| async move {
+            let content = fetch_res(fetcher, base_url, &entry, progress)
+                .await
+                .map_err(|e| ConvertError::download(&entry.key, &display_res_url(&entry.url), e))?;
+            Ok::<_, ConvertError>(DownloadResult {
+                res_type,
+                content,
+                from_pack: true,
+            })
+        })
+        .buffer_unordered(options.jobs.max(1))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    progress.finish();
+    Ok(downloaded)
+}
@@ -0,0 +1,155 @@
+use bytes::Bytes;
+use image::{Rgba, RgbaImage};
+
+use crate::atlas::{encode_png, load_rgba8};
+use crate::convert::DownloadResult;
+use crate::error::ConvertError;
+use crate::meta::{ImageResType, ResType};
+
+pub const DEFAULT_HIGHLIGHT_COLOR: u32 = 0xffffeb8c;
+pub const DEFAULT_HIGHLIGHT_WIDTH: u32 = 8;
+
+const HIGHLIGHT_SOURCES: [(ImageResType, ImageResType); 6] = [
+    (ImageResType::TapHL, ImageResType::Tap),
+    (ImageResType::DragHL, ImageResType::Drag),
+    (ImageResType::FlickHL, ImageResType::Flick),
+    (ImageResType::HoldEndHL, ImageResType::HoldEnd),
+    (ImageResType::HoldHL, ImageResType::Hold),
+    (ImageResType::HoldHeadHL, ImageResType::HoldHead),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HighlightStyle {
+    /// A soft halo fading out over `width` pixels.
+    #[default]
+    Glow,
+    /// A solid band `width` pixels wide.
+    Outline,
+}
+
+#[derive(Debug, Clone)]
+pub struct HighlightOptions {
+    pub style: HighlightStyle,
+    /// ARGB, like `colorPerfect` in `info.yml`.
+    pub color: u32,
+    pub width: u32,
+}
+
+impl Default for HighlightOptions {
+    fn default() -> Self {
+        HighlightOptions {
+            style: HighlightStyle::default(),
+            color: DEFAULT_HIGHLIGHT_COLOR,
+            width: DEFAULT_HIGHLIGHT_WIDTH,
+        }
+    }
+}
+
+/// Approximate distance (in pixels) from every pixel to the nearest mostly opaque one,
+/// using a two-pass chamfer transform.
+fn distance_to_opaque(img: &RgbaImage) -> Vec<f32> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut distance: Vec<f32> = img
+        .pixels()
+        .map(|pixel| if pixel[3] >= 128 { 0.0 } else { f32::INFINITY })
+        .collect();
+
+    let neighbours = [(-1, 0, 1.0), (0, -1, 1.0), (-1, -1, std::f32::consts::SQRT_2), (1, -1, std::f32::consts::SQRT_2)];
+    let mut relax = |x: usize, y: usize, sign: isize| {
+        let index = y * width + x;
+        for (dx, dy, cost) in neighbours {
+            let nx = x as isize + dx * sign;
+            let ny = y as isize + dy * sign;
+            if nx >= 0 && ny >= 0 && (nx as usize) < width && (ny as usize) < height {
+                let candidate = distance[ny as usize * width + nx as usize] + cost;
+                if candidate < distance[index] {
+                    distance[index] = candidate;
+                }
+            }
+        }
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            relax(x, y, 1);
+        }
+    }
+    for y in (0..height).rev() {
+        for x in (0..width).rev() {
+            relax(x, y, -1);
+        }
+    }
+
+    distance
+}
+
+fn blend_over(top: Rgba<u8>, bottom: Rgba<u8>) -> Rgba<u8> {
+    let top_alpha = top[3] as f32 / 255.0;
+    let bottom_alpha = bottom[3] as f32 / 255.0;
+    let alpha = top_alpha + bottom_alpha * (1.0 - top_alpha);
+    if alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let channel = |i: usize| {
+        let value = (top[i] as f32 * top_alpha + bottom[i] as f32 * bottom_alpha * (1.0 - top_alpha)) / alpha;
+        value.round() as u8
+    };
+    Rgba([channel(0), channel(1), channel(2), (alpha * 255.0).round() as u8])
+}
+
+/// Draws a glow or outline around the opaque parts of `img`. The canvas grows by the
+/// highlight width on each side, or only horizontally for sprites that are stretched vertically.
+pub fn highlight_image(img: &RgbaImage, options: &HighlightOptions, pad_vertical: bool) -> RgbaImage {
+    let pad_x = options.width;
+    let pad_y = if pad_vertical { options.width } else { 0 };
+
+    let mut canvas = RgbaImage::new(img.width() + pad_x * 2, img.height() + pad_y * 2);
+    image::imageops::replace(&mut canvas, img, pad_x as i64, pad_y as i64);
+
+    let distance = distance_to_opaque(&canvas);
+    let [alpha, r, g, b] = options.color.to_be_bytes();
+    let radius = options.width.max(1) as f32;
+
+    for (pixel, distance) in canvas.pixels_mut().zip(distance) {
+        let strength = match options.style {
+            HighlightStyle::Glow if distance > 0.0 && distance <= radius => (1.0 - distance / radius).powi(2),
+            HighlightStyle::Outline if distance > 0.0 => (radius + 0.5 - distance).clamp(0.0, 1.0),
+            _ => 0.0,
+        };
+        let halo = Rgba([r, g, b, (alpha as f32 * strength).round() as u8]);
+        *pixel = blend_over(*pixel, halo);
+    }
+
+    canvas
+}
+
+/// Builds the highlighted variants a pack lacks from its plain sprites, returning the
+/// resource types that were generated. `premultiplied` applies to sprites from the pack itself.
+pub(crate) fn generate_highlights(downloads: &mut Vec<DownloadResult>, options: &HighlightOptions, premultiplied: bool) -> Result<Vec<ResType>, ConvertError> {
+    let mut generated = Vec::new();
+
+    for (hl_type, base_type) in HIGHLIGHT_SOURCES {
+        let has = |img_type: &ImageResType| downloads.iter().any(|res| res.res_type == ResType::Image(img_type.clone()));
+        if has(&hl_type) {
+            continue;
+        }
+        let Some(base) = downloads.iter().find(|res| res.res_type == ResType::Image(base_type.clone())) else {
+            continue;
+        };
+
+        let img = load_rgba8(&base.content, &base_type, premultiplied && base.from_pack)?;
+        let pad_vertical = base_type != ImageResType::Hold;
+        let highlighted = highlight_image(&img, options, pad_vertical);
+
+        let res_type = ResType::Image(hl_type.clone());
+        downloads.push(DownloadResult {
+            res_type: res_type.clone(),
+            content: Bytes::from(encode_png(&highlighted, &hl_type)?),
+            from_pack: false,
+        });
+        generated.push(res_type);
+    }
+
+    Ok(generated)
+}
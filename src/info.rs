@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::convert::ProcessedRes;
+use crate::error::ConvertError;
+use crate::meta::{ImageResType, PTRespackMeta, ResType};
+
+pub const INFO_FILENAME: &str = "info.yml";
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResPackOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_fx_duration: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_fx_scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_fx_rotate: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_fx_tinted: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hide_particles: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_keep_head: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_repeat: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_compact: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_perfect: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_good: Option<u32>,
+}
+
+impl ResPackOptions {
+    pub fn merge(self, overrides: ResPackOptions) -> ResPackOptions {
+        ResPackOptions {
+            hit_fx_duration: overrides.hit_fx_duration.or(self.hit_fx_duration),
+            hit_fx_scale: overrides.hit_fx_scale.or(self.hit_fx_scale),
+            hit_fx_rotate: overrides.hit_fx_rotate.or(self.hit_fx_rotate),
+            hit_fx_tinted: overrides.hit_fx_tinted.or(self.hit_fx_tinted),
+            hide_particles: overrides.hide_particles.or(self.hide_particles),
+            hold_keep_head: overrides.hold_keep_head.or(self.hold_keep_head),
+            hold_repeat: overrides.hold_repeat.or(self.hold_repeat),
+            hold_compact: overrides.hold_compact.or(self.hold_compact),
+            color_perfect: overrides.color_perfect.or(self.color_perfect),
+            color_good: overrides.color_good.or(self.color_good),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InfoOverrides {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub options: ResPackOptions,
+}
+
+impl InfoOverrides {
+    pub fn load(path: &Path) -> Result<InfoOverrides, ConvertError> {
+        let data = fs::read(path)?;
+        serde_yaml::from_slice(&data)
+            .map_err(|e| ConvertError::Validation(format!("Invalid info config {}: {}", path.display(), e)))
+    }
+
+    pub fn merge(self, overrides: InfoOverrides) -> InfoOverrides {
+        InfoOverrides {
+            name: overrides.name.or(self.name),
+            author: overrides.author.or(self.author),
+            description: overrides.description.or(self.description),
+            options: self.options.merge(overrides.options),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResPackInfo {
+    pub name: String,
+    pub author: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hit_fx: Option<(u32, u32)>,
+    
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_atlas: Option<(u32, u32)>,
+    #[serde(rename = "holdAtlasMH")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold_atlas_mh: Option<(u32, u32)>,
+
+    #[serde(flatten)]
+    pub options: ResPackOptions,
+
+    #[serde(default)]
+    pub description: String,
+}
+
+pub(crate) fn generate_respack_info(meta: PTRespackMeta, processed: &ProcessedRes, overrides: &InfoOverrides) -> ResPackInfo {
+    let written = |res_type: ImageResType, value: Option<(u32, u32)>| {
+        value.filter(|_| processed.contains(&ResType::Image(res_type)))
+    };
+    let overrides = overrides.clone();
+
+    ResPackInfo {
+        name: overrides.name.unwrap_or(meta.name),
+        author: overrides.author.unwrap_or(meta.author),
+        hit_fx: written(ImageResType::HitFX, processed.hit_fx),
+        hold_atlas: written(ImageResType::CombinedHold, processed.hold_atlas),
+        hold_atlas_mh: written(ImageResType::CombinedHoldHL, processed.hold_atlas_mh),
+        options: meta.options.merge(overrides.options),
+        description: overrides.description.or(meta.description).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn atlas_values_require_the_written_file() {
+        let meta = PTRespackMeta {
+            name: "test".to_string(),
+            author: "tester".to_string(),
+            res: BTreeMap::new(),
+            hit_fx_frames: None,
+            description: None,
+            options: ResPackOptions::default(),
+        };
+        let mut processed = ProcessedRes {
+            hit_fx: Some((5, 6)),
+            hold_atlas: Some((10, 20)),
+            hold_atlas_mh: Some((11, 21)),
+            ..ProcessedRes::default()
+        };
+        processed.files.insert("hold.png".to_string(), Bytes::new());
+
+        let info = generate_respack_info(meta, &processed, &InfoOverrides::default());
+        assert_eq!(info.hit_fx, None);
+        assert_eq!(info.hold_atlas, Some((10, 20)));
+        assert_eq!(info.hold_atlas_mh, None);
+    }
+}
@@ -0,0 +1,31 @@
+mod alias;
+mod atlas;
+mod audio;
+mod convert;
+mod dsp;
+mod error;
+mod fetch;
+mod highlight;
+mod info;
+mod local;
+mod meta;
+mod output;
+mod progress;
+mod report;
+mod resolve;
+mod skin;
+
+pub use alias::ResAliases;
+pub use atlas::{combine_hold_images, hit_fx_convector, hit_fx_grid, hit_fx_grid_image, hit_fx_grid_to_strip, split_hold_atlas, ImageOptions};
+pub use audio::{AudioFormat, AudioOptions, ChannelLayout, Normalization, DEFAULT_AUDIO_QUALITY};
+pub use convert::{ConvertOptions, ConvertedPack, Converter, InspectReport, InspectedRes, ReversedPack};
+pub use error::ConvertError;
+pub use fetch::{DEFAULT_CACHE_DIR, DEFAULT_DOWNLOAD_JOBS, DEFAULT_RETRIES, DEFAULT_TIMEOUT};
+pub use highlight::{highlight_image, HighlightOptions, HighlightStyle, DEFAULT_HIGHLIGHT_COLOR, DEFAULT_HIGHLIGHT_WIDTH};
+pub use info::{InfoOverrides, ResPackInfo, ResPackOptions, INFO_FILENAME};
+pub use local::is_remote_input;
+pub use meta::{get_filename, AudioResType, ImageResType, PTRespackMeta, ResType};
+pub use output::{build_respack_zip, sanitize_pack_name, OutputFormat, PackWriter, DEFAULT_OUTPUT_ROOT};
+pub use progress::format_bytes;
+pub use report::{AliasMatch, ConvertReport};
+pub use skin::DefaultSkin;
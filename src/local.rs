@@ -0,0 +1,227 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::convert::DownloadResult;
+use crate::error::{BoxError, ConvertError};
+use crate::info::{ResPackInfo, INFO_FILENAME};
+use crate::meta::{PTRespackMeta, ResEntry, ResType};
+use crate::resolve::{decode_data_uri, display_res_url, is_data_uri};
+
+pub(crate) const LOCAL_META_FILENAMES: [&str; 2] = ["meta.json", "respack.json"];
+
+pub(crate) enum LocalRespack {
+    Dir(PathBuf),
+    Zip {
+        archive: zip::ZipArchive<fs::File>,
+        root: String,
+    },
+}
+
+pub fn is_remote_input(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+fn normalize_local_res_path(path: &str) -> Result<Vec<&str>, ConvertError> {
+    if path.starts_with('/') || path.starts_with('\\') || path.contains(':') {
+        return Err(ConvertError::Validation(format!("Resource path must be relative: {}", path)));
+    }
+
+    let mut parts = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {},
+            ".." => return Err(ConvertError::Validation(format!("Resource path escapes the pack: {}", path))),
+            _ => parts.push(part),
+        }
+    }
+
+    if parts.is_empty() {
+        return Err(ConvertError::Validation(format!("Empty resource path: {:?}", path)));
+    }
+    Ok(parts)
+}
+
+fn find_meta_in_dir(dir: &Path) -> Result<PathBuf, BoxError> {
+    for filename in LOCAL_META_FILENAMES {
+        let candidate = dir.join(filename);
+        if candidate.is_file() {
+            return Ok(candidate);
+        }
+    }
+
+    let json_files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")))
+        .collect();
+
+    match json_files.as_slice() {
+        [meta] => Ok(meta.clone()),
+        [] => Err(format!("No meta JSON found in {}", dir.display()).into()),
+        _ => Err(format!("Multiple JSON files in {}, expected one of {:?}", dir.display(), LOCAL_META_FILENAMES).into()),
+    }
+}
+
+fn find_meta_in_zip(names: &[&str]) -> Result<String, BoxError> {
+    fn depth(name: &str) -> usize {
+        name.matches('/').count()
+    }
+    fn basename(name: &str) -> String {
+        name.rsplit('/').next().unwrap_or(name).to_lowercase()
+    }
+
+    let known = names
+        .iter()
+        .filter(|name| LOCAL_META_FILENAMES.contains(&basename(name).as_str()))
+        .min_by_key(|name| depth(name));
+    if let Some(meta) = known {
+        return Ok(meta.to_string());
+    }
+
+    let json_files: Vec<&str> = names
+        .iter()
+        .copied()
+        .filter(|name| basename(name).ends_with(".json"))
+        .collect();
+    let min_depth = json_files.iter().map(|name| depth(name)).min();
+    let shallowest: Vec<&str> = json_files
+        .into_iter()
+        .filter(|name| Some(depth(name)) == min_depth)
+        .collect();
+
+    match shallowest.as_slice() {
+        [meta] => Ok(meta.to_string()),
+        [] => Err("No meta JSON found in archive".into()),
+        _ => Err(format!("Multiple JSON files in archive, expected one of {:?}", LOCAL_META_FILENAMES).into()),
+    }
+}
+
+pub(crate) fn open_local_respack(path: &Path) -> Result<(LocalRespack, PTRespackMeta), ConvertError> {
+    load_local_respack(path).map_err(|e| ConvertError::meta(path.display(), e))
+}
+
+fn load_local_respack(path: &Path) -> Result<(LocalRespack, PTRespackMeta), BoxError> {
+    if path.is_dir() {
+        let meta_path = find_meta_in_dir(path)?;
+        let meta = serde_json::from_slice(&fs::read(&meta_path)?)?;
+        return Ok((LocalRespack::Dir(path.to_path_buf()), meta));
+    }
+
+    let is_zip = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    if !is_zip {
+        let meta = serde_json::from_slice(&fs::read(path)?)?;
+        let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        return Ok((LocalRespack::Dir(dir), meta));
+    }
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let meta_name = {
+        let names: Vec<&str> = archive.file_names().collect();
+        find_meta_in_zip(&names)?
+    };
+    let root = match meta_name.rfind('/') {
+        Some(index) => meta_name[..=index].to_string(),
+        None => String::new(),
+    };
+
+    let mut meta_data = Vec::new();
+    archive.by_name(&meta_name)?.read_to_end(&mut meta_data)?;
+    let meta = serde_json::from_slice(&meta_data)?;
+
+    Ok((LocalRespack::Zip { archive, root }, meta))
+}
+
+impl LocalRespack {
+    pub(crate) fn read(&mut self, res_path: &str) -> Result<Bytes, ConvertError> {
+        let parts = normalize_local_res_path(res_path)?;
+
+        match self {
+            LocalRespack::Dir(dir) => {
+                let path = parts.iter().fold(dir.clone(), |path, part| path.join(part));
+                Ok(Bytes::from(fs::read(path)?))
+            },
+            LocalRespack::Zip { archive, root } => {
+                let name = format!("{}{}", root, parts.join("/"));
+                let mut data = Vec::new();
+                archive.by_name(&name)?.read_to_end(&mut data)?;
+                Ok(Bytes::from(data))
+            },
+        }
+    }
+
+    pub(crate) fn read_optional(&mut self, res_path: &str) -> Result<Option<Bytes>, ConvertError> {
+        let exists = match self {
+            LocalRespack::Dir(dir) => {
+                let parts = normalize_local_res_path(res_path)?;
+                parts.iter().fold(dir.clone(), |path, part| path.join(part)).is_file()
+            },
+            LocalRespack::Zip { archive, root } => {
+                let parts = normalize_local_res_path(res_path)?;
+                archive.index_for_name(&format!("{}{}", root, parts.join("/"))).is_some()
+            },
+        };
+
+        if exists {
+            self.read(res_path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+pub(crate) fn read_local_res(pack: &mut LocalRespack, res_urls: HashMap<ResType, ResEntry>) -> Result<Vec<DownloadResult>, ConvertError> {
+    let mut loaded = Vec::new();
+
+    for (res_type, entry) in res_urls {
+        let content = if is_data_uri(&entry.url) {
+            decode_data_uri(&entry.url)
+        } else {
+            pack.read(&entry.url).map_err(BoxError::from)
+        };
+        let content = content.map_err(|e| ConvertError::download(&entry.key, &display_res_url(&entry.url), e))?;
+        loaded.push(DownloadResult {
+            res_type,
+            content,
+            from_pack: true,
+        });
+    }
+
+    Ok(loaded)
+}
+
+fn find_info_in_zip(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter(|name| name.rsplit('/').next() == Some(INFO_FILENAME))
+        .min_by_key(|name| name.matches('/').count())
+        .map(|name| name.to_string())
+}
+
+pub(crate) fn open_prpr_respack(path: &Path) -> Result<(LocalRespack, ResPackInfo), ConvertError> {
+    load_prpr_respack(path).map_err(|e| ConvertError::meta(path.display(), e))
+}
+
+fn load_prpr_respack(path: &Path) -> Result<(LocalRespack, ResPackInfo), BoxError> {
+    if path.is_dir() {
+        let info = serde_yaml::from_slice(&fs::read(path.join(INFO_FILENAME))?)?;
+        return Ok((LocalRespack::Dir(path.to_path_buf()), info));
+    }
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let info_name = {
+        let names: Vec<&str> = archive.file_names().collect();
+        find_info_in_zip(&names).ok_or_else(|| format!("No {} found in {}", INFO_FILENAME, path.display()))?
+    };
+    let root = match info_name.rfind('/') {
+        Some(index) => info_name[..=index].to_string(),
+        None => String::new(),
+    };
+
+    let mut info_data = Vec::new();
+    archive.by_name(&info_name)?.read_to_end(&mut info_data)?;
+    let info = serde_yaml::from_slice(&info_data)?;
+
+    Ok((LocalRespack::Zip { archive, root }, info))
+}
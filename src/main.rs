@@ -1,386 +1,826 @@
-use reqwest::Error;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
-use std::path::Path;
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
 use bytes::Bytes;
-use image::{ImageBuffer, GenericImageView, DynamicImage, ImageEncoder};
-use std::env;
-
-const PTRESPACK_META_URL: &str = "https://pgres4pt.realtvop.top/fish";
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use ptonlineres2prpr::{
+    format_bytes, AudioOptions, ChannelLayout, ConvertError, ConvertOptions, ConvertReport, Converter, DefaultSkin,
+    HighlightOptions, HighlightStyle, ImageOptions, InfoOverrides, Normalization, OutputFormat, PackWriter, ResAliases, ResPackOptions,
+    DEFAULT_AUDIO_QUALITY, DEFAULT_CACHE_DIR, DEFAULT_DOWNLOAD_JOBS, DEFAULT_HIGHLIGHT_WIDTH, DEFAULT_OUTPUT_ROOT, DEFAULT_RETRIES, DEFAULT_TIMEOUT,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+const DEFAULT_BATCH_PARALLEL: usize = 2;
+
+#[derive(Parser)]
+#[command(version, about = "Convert PhiTogether online resource packs to prpr format")]
+#[command(after_help = "Example: ptonlineres2prpr convert https://pgres4pt.realtvop.top/fish")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Print every written file
+    #[arg(long, short, global = true)]
+    verbose: bool,
+
+    /// Print results as JSON on stdout
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Do not print download progress
+    #[arg(long, short, global = true)]
+    quiet: bool,
+}
 
-#[derive(Debug, Deserialize)]
-struct PTRespackMeta {
-    name: String,
-    author: String,
-    // includes_hit_songs: bool,
-    res: HashMap<String, String>,
-}
-
-async fn fetch_meta(url: &str) -> Result<PTRespackMeta, Error> {
-    let client = reqwest::Client::new();
-    
-    let response = client.get(url)
-        .send()
-        .await?;
-    
-    let meta = response.json::<PTRespackMeta>().await?;
-    Ok(meta)
-}
-
-#[derive(Debug, Eq, Hash, PartialEq, Clone)]
-enum ImageResType {
-    HitFX,
-    Tap,
-    TapHL,
-    HoldEnd,
-    Hold,
-    HoldHL,
-    HoldHead,
-    HoldHeadHL,
-    CombinedHold,
-    CombinedHoldHL,
-    Drag,
-    DragHL,
-    Flick,
-    FlickHL,
-}
-#[derive(Debug, Eq, Hash, PartialEq, Clone)]
-enum AudioResType {
-    TapHitSound,
-    DragHitSound,
-    FlickHitSound,
-}
-#[derive(Debug, Eq, Hash, PartialEq, Clone)]
-enum ResType {
-    Image(ImageResType),
-    Audio(AudioResType),
-}
-const IMAGE_RES_MAPPINGS: [(&[&str], ImageResType); 12] = [
-    (&["clickraw", "clickraw.png"], ImageResType::HitFX),
-    (&["tap", "tap.png"], ImageResType::Tap),
-    (&["taphl", "taphl.png"], ImageResType::TapHL),
-    (&["holdend", "holdend.png"], ImageResType::HoldEnd),
-    (&["hold", "hold.png"], ImageResType::Hold),
-    (&["holdhl", "holdhl.png"], ImageResType::HoldHL),
-    (&["holdhead", "holdhead.png"], ImageResType::HoldHead),
-    (&["holdheadhl", "holdheadhl.png"], ImageResType::HoldHeadHL),
-    (&["drag", "drag.png"], ImageResType::Drag),
-    (&["draghl", "draghl.png"], ImageResType::DragHL),
-    (&["flick", "flick.png"], ImageResType::Flick),
-    (&["flickhl", "flickhl.png"], ImageResType::FlickHL),
-];
-
-const AUDIO_RES_MAPPINGS: [(&[&str], AudioResType); 3] = [
-    (&["hitsong0", "hitsong0.ogg"], AudioResType::TapHitSound),
-    (&["hitsong1", "hitsong1.ogg"], AudioResType::DragHitSound),
-    (&["hitsong2", "hitsong2.ogg"], AudioResType::FlickHitSound),
-];
-
-fn res_name_parser(res: &HashMap<String, String>) -> HashMap<ResType, String> {
-    let mut res_urls = HashMap::<ResType, String>::new();
-    
-    for (name, url) in res {
-        let name_lower = name.to_lowercase();
-        
-        if let Some((_, img_type)) = IMAGE_RES_MAPPINGS
-            .iter()
-            .find(|(names, _)| names.contains(&name_lower.as_str())) {
-            res_urls.insert(ResType::Image(img_type.clone()), url.clone());
-        }
-        
-        if let Some((_, audio_type)) = AUDIO_RES_MAPPINGS
-            .iter()
-            .find(|(names, _)| names.contains(&name_lower.as_str())) {
-            res_urls.insert(ResType::Audio(audio_type.clone()), url.clone());
-        }
-    }
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a PhiTogether pack (URL, meta.json, directory or .zip) to a prpr respack
+    Convert {
+        input: String,
+
+        /// Convert a prpr respack back into a PhiTogether pack
+        #[arg(long)]
+        reverse: bool,
+
+        /// Write to this path instead of a path under output/
+        #[arg(long)]
+        out: Option<PathBuf>,
+
+        /// Write the conversion report to this file (JSON if it ends in .json, text otherwise)
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        fetch: FetchArgs,
+        #[command(flatten)]
+        info: InfoArgs,
+        #[command(flatten)]
+        skin: SkinArgs,
+        #[command(flatten)]
+        image: ImageArgs,
+        #[command(flatten)]
+        audio: AudioArgs,
+    },
+    /// Show the meta and resources of a PhiTogether pack
+    Inspect {
+        input: String,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
+    },
+    /// Load and convert a PhiTogether pack without writing anything
+    Validate {
+        input: String,
+
+        /// Write the conversion report to this file (JSON if it ends in .json, text otherwise)
+        #[arg(long)]
+        report: Option<PathBuf>,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
+        #[command(flatten)]
+        info: InfoArgs,
+        #[command(flatten)]
+        skin: SkinArgs,
+        #[command(flatten)]
+        image: ImageArgs,
+        #[command(flatten)]
+        audio: AudioArgs,
+    },
+    /// Convert every pack listed in a file, one input per line
+    Batch {
+        /// File listing the inputs, or - to read them from stdin
+        list: String,
+
+        /// Directory the converted packs are written to
+        #[arg(long, default_value = DEFAULT_OUTPUT_ROOT)]
+        out_dir: PathBuf,
+
+        /// Convert at most this many packs at once
+        #[arg(long, default_value_t = DEFAULT_BATCH_PARALLEL)]
+        parallel: usize,
+
+        /// Also write the summary to this file (JSON if it ends in .json, a table otherwise)
+        #[arg(long)]
+        summary: Option<PathBuf>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+        #[command(flatten)]
+        fetch: FetchArgs,
+        #[command(flatten)]
+        info: InfoArgs,
+        #[command(flatten)]
+        skin: SkinArgs,
+        #[command(flatten)]
+        image: ImageArgs,
+        #[command(flatten)]
+        audio: AudioArgs,
+    },
+}
 
-    res_urls
+#[derive(Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Dir,
+    Zip,
 }
 
-fn get_output_dir(name: &str) -> std::path::PathBuf {
-    Path::new("output").join(name)
+#[derive(Args)]
+struct OutputArgs {
+    /// Output layout
+    #[arg(long, value_enum, default_value_t = FormatArg::Dir)]
+    format: FormatArg,
+
+    /// Replace an existing output instead of picking a new name
+    #[arg(long)]
+    overwrite: bool,
+
+    /// Convert everything but do not write the output
+    #[arg(long)]
+    dry_run: bool,
 }
 
-async fn ensure_directories(name: &str) -> std::io::Result<()> {
-    fs::create_dir_all(get_output_dir(name))?;
-    Ok(())
+#[derive(Args)]
+struct FetchArgs {
+    /// Download at most this many resources at once
+    #[arg(long, default_value_t = DEFAULT_DOWNLOAD_JOBS)]
+    jobs: usize,
+
+    /// Timeout for each request, in seconds
+    #[arg(long, default_value_t = DEFAULT_TIMEOUT.as_secs_f64())]
+    timeout: f64,
+
+    /// Retries for transient network failures
+    #[arg(long, default_value_t = DEFAULT_RETRIES)]
+    retries: u32,
+
+    /// Download cache directory
+    #[arg(long, default_value = DEFAULT_CACHE_DIR)]
+    cache_dir: PathBuf,
+
+    /// Do not read or write the download cache
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Only use the download cache, never the network
+    #[arg(long)]
+    offline: bool,
+
+    /// YAML or TOML file with extra resource name aliases
+    #[arg(long)]
+    aliases: Option<PathBuf>,
 }
 
-async fn download_file(client: &reqwest::Client, url: &str) -> Result<bytes::Bytes, Error> {
-    let response = client.get(url).send().await?;
-    let bytes = response.bytes().await?;
-    Ok(bytes)
+#[derive(Args)]
+struct InfoArgs {
+    /// YAML file with info.yml fields to override
+    #[arg(long)]
+    info_config: Option<PathBuf>,
+
+    /// Override the pack name
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Override the pack author
+    #[arg(long)]
+    author: Option<String>,
+
+    /// Override the pack description
+    #[arg(long)]
+    description: Option<String>,
+
+    /// Set hitFxDuration, in seconds
+    #[arg(long)]
+    hit_fx_duration: Option<f32>,
+
+    /// Set hitFxScale
+    #[arg(long)]
+    hit_fx_scale: Option<f32>,
+
+    /// Set hitFxRotate (true when given without a value)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    hit_fx_rotate: Option<bool>,
+
+    /// Set hitFxTinted (true when given without a value)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    hit_fx_tinted: Option<bool>,
+
+    /// Set hideParticles (true when given without a value)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    hide_particles: Option<bool>,
+
+    /// Set holdKeepHead (true when given without a value)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    hold_keep_head: Option<bool>,
+
+    /// Set holdRepeat (true when given without a value)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    hold_repeat: Option<bool>,
+
+    /// Set holdCompact (true when given without a value)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    hold_compact: Option<bool>,
+
+    /// Set colorPerfect (#RRGGBB, #AARRGGBB or 0xAARRGGBB)
+    #[arg(long, value_parser = parse_color)]
+    color_perfect: Option<u32>,
+
+    /// Set colorGood (#RRGGBB, #AARRGGBB or 0xAARRGGBB)
+    #[arg(long, value_parser = parse_color)]
+    color_good: Option<u32>,
 }
 
-async fn save_file(path: &Path, contents: bytes::Bytes) -> std::io::Result<()> {
-    let mut file = File::create(path).await?;
-    file.write_all(&contents).await?;
-    Ok(())
+#[derive(Args)]
+struct SkinArgs {
+    /// Fill missing resources from a default skin: "builtin" or a local PhiTogether pack
+    #[arg(long, value_parser = parse_default_skin)]
+    default_skin: Option<DefaultSkin>,
+
+    /// Generate missing highlight (multi-hit) sprites from the plain ones
+    #[arg(long)]
+    generate_highlights: bool,
+
+    /// Highlight effect drawn around generated highlight sprites
+    #[arg(long, value_enum, default_value_t = HighlightStyleArg::Glow)]
+    highlight_style: HighlightStyleArg,
+
+    /// Highlight color (#RRGGBB, #AARRGGBB or 0xAARRGGBB)
+    #[arg(long, value_parser = parse_color, default_value = "#FFEB8C")]
+    highlight_color: u32,
+
+    /// Highlight width, in pixels
+    #[arg(long, default_value_t = DEFAULT_HIGHLIGHT_WIDTH)]
+    highlight_width: u32,
 }
 
-fn get_filename(res_type: &ResType) -> &'static str {
-    match res_type {
-        ResType::Image(img_type) => match img_type {
-            ImageResType::HitFX => "hit_fx.png",
-            ImageResType::Tap => "click.png",
-            ImageResType::TapHL => "click_mh.png",
-            ImageResType::Drag => "drag.png",
-            ImageResType::DragHL => "drag_mh.png",
-            ImageResType::Flick => "flick.png",
-            ImageResType::FlickHL => "flick_mh.png",
-            ImageResType::CombinedHold => "hold.png",
-            ImageResType::CombinedHoldHL => "hold_mh.png",
-            
-            _ => "",
-        },
-        ResType::Audio(audio_type) => match audio_type {
-            AudioResType::TapHitSound => "click.ogg",
-            AudioResType::DragHitSound => "drag.ogg",
-            AudioResType::FlickHitSound => "flick.ogg",
-        },
-    }
+#[derive(Args)]
+struct ImageArgs {
+    /// Treat the pack's own images as having premultiplied alpha
+    #[arg(long)]
+    premultiplied_alpha: bool,
+
+    /// Keep 16 bits per channel in converted images when the source has them
+    #[arg(long)]
+    preserve_16bit: bool,
 }
 
-struct DownloadResult {
-    res_type: ResType,
-    content: Bytes,
-}
-
-fn hit_fx_convector(image_data: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let img = image::load_from_memory(image_data)?;
-    
-    let orig_width = img.width();
-    let orig_height = img.height();
-    
-    let frame_width = orig_width;
-    let frame_height = orig_height / 30;
-    
-    let new_width = frame_width * 5;
-    let new_height = frame_height * 6;
-
-    let mut new_image = ImageBuffer::new(new_width, new_height);
-    
-    for i in 0..30 {
-        let old_y = (i as u32) * frame_height;
-        
-        let new_x = ((i as u32) % 5) * frame_width;
-        let new_y = ((i as u32) / 5) * frame_height;
-
-        for y in 0..frame_height {
-            for x in 0..frame_width {
-                let pixel = img.get_pixel(x, old_y + y);
-                new_image.put_pixel(new_x + x, new_y + y, pixel);
-            }
-        }
-    }
+#[derive(Args)]
+struct AudioArgs {
+    /// Ogg Vorbis quality for re-encoded hit sounds, from -1 to 10
+    #[arg(long, default_value_t = DEFAULT_AUDIO_QUALITY, allow_negative_numbers = true)]
+    audio_quality: f32,
+
+    /// Trim leading silence quieter than this many dBFS, e.g. -50
+    #[arg(long, allow_negative_numbers = true)]
+    trim_silence: Option<f32>,
+
+    /// Normalize hit sounds to this sample peak, in dBFS
+    #[arg(long, allow_negative_numbers = true, conflicts_with = "normalize_lufs")]
+    normalize_peak: Option<f32>,
+
+    /// Normalize hit sounds to this integrated loudness, in LUFS
+    #[arg(long, allow_negative_numbers = true)]
+    normalize_lufs: Option<f32>,
 
-    let mut output = Vec::new();
-    let encoder = image::codecs::png::PngEncoder::new(&mut output);
-    encoder.write_image(
-        new_image.as_raw(),
-        new_width,
-        new_height,
-        image::ColorType::Rgba8
-    )?;
-    Ok(output)
+    /// Resample hit sounds to this sample rate, in Hz
+    #[arg(long)]
+    sample_rate: Option<u32>,
+
+    /// Convert hit sounds to this channel layout
+    #[arg(long, value_enum)]
+    channels: Option<ChannelsArg>,
 }
 
-fn combine_hold_images(holdend: &[u8], hold: &[u8], holdhead: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let end_img = image::load_from_memory(holdend)?;
-    let hold_img = image::load_from_memory(hold)?;
-    let head_img = image::load_from_memory(holdhead)?;
+#[derive(Clone, Copy, ValueEnum)]
+enum ChannelsArg {
+    Mono,
+    Stereo,
+}
 
-    let width = end_img.width().max(hold_img.width()).max(head_img.width());
-    let height = end_img.height() + hold_img.height() + head_img.height();
+#[derive(Clone, Copy, ValueEnum)]
+enum HighlightStyleArg {
+    Glow,
+    Outline,
+}
+
+fn parse_default_skin(value: &str) -> Result<DefaultSkin, String> {
+    if value.eq_ignore_ascii_case("builtin") {
+        Ok(DefaultSkin::Builtin)
+    } else {
+        Ok(DefaultSkin::Pack(PathBuf::from(value)))
+    }
+}
 
-    let mut combined = ImageBuffer::new(width, height);
+fn parse_color(value: &str) -> Result<u32, String> {
+    let (hex, short_is_rgb) = if let Some(hex) = value.strip_prefix('#') {
+        (hex, true)
+    } else if let Some(hex) = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        (hex, false)
+    } else {
+        (value, false)
+    };
 
-    let x_offset = (width - end_img.width()) / 2;
-    for y in 0..end_img.height() {
-        for x in 0..end_img.width() {
-            combined.put_pixel(x_offset + x, y, end_img.get_pixel(x, y));
-        }
+    let color = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid color: {}", value))?;
+    match hex.len() {
+        6 if short_is_rgb => Ok(0xff000000 | color),
+        8 => Ok(color),
+        _ => Err(format!("Invalid color: {}", value)),
     }
+}
 
-    let x_offset = (width - hold_img.width()) / 2;
-    let y_offset = end_img.height();
-    for y in 0..hold_img.height() {
-        for x in 0..hold_img.width() {
-            combined.put_pixel(x_offset + x, y_offset + y, hold_img.get_pixel(x, y));
+impl FetchArgs {
+    fn apply(&self, options: &mut ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+        if self.jobs == 0 {
+            return Err("--jobs must be at least 1".into());
+        }
+        if !(self.timeout.is_finite() && self.timeout > 0.0) {
+            return Err(format!("Invalid value for --timeout: {}", self.timeout).into());
         }
-    }
 
-    let x_offset = (width - head_img.width()) / 2;
-    let y_offset = end_img.height() + hold_img.height();
-    for y in 0..head_img.height() {
-        for x in 0..head_img.width() {
-            combined.put_pixel(x_offset + x, y_offset + y, head_img.get_pixel(x, y));
+        options.jobs = self.jobs;
+        options.timeout = Duration::from_secs_f64(self.timeout);
+        options.retries = self.retries;
+        options.cache_dir = (!self.no_cache).then(|| self.cache_dir.clone());
+        options.offline = self.offline;
+        if let Some(path) = &self.aliases {
+            options.aliases = ResAliases::load(path)?;
         }
+        Ok(())
     }
+}
 
-    let mut output = Vec::new();
-    let encoder = image::codecs::png::PngEncoder::new(&mut output);
-    encoder.write_image(
-        combined.as_raw(),
-        width,
-        height,
-        image::ColorType::Rgba8
-    )?;
-    Ok(output)
-}
-
-async fn download_res(res_urls: HashMap<ResType, String>) -> Result<Vec<DownloadResult>, Box<dyn std::error::Error>> {
-    let client = reqwest::Client::new();
-    let mut downloaded = Vec::new();
-
-    for (res_type, url) in res_urls {
-        let bytes = download_file(&client, &url).await?;
-        downloaded.push(DownloadResult {
-            res_type,
-            content: bytes,
+impl SkinArgs {
+    fn apply(&self, options: &mut ConvertOptions) {
+        options.default_skin = self.default_skin.clone();
+        options.highlight = self.generate_highlights.then_some(HighlightOptions {
+            style: match self.highlight_style {
+                HighlightStyleArg::Glow => HighlightStyle::Glow,
+                HighlightStyleArg::Outline => HighlightStyle::Outline,
+            },
+            color: self.highlight_color,
+            width: self.highlight_width,
         });
     }
-
-    Ok(downloaded)
 }
 
-async fn save_res(downloads: Vec<DownloadResult>, meta: PTRespackMeta) -> Result<(), Box<dyn std::error::Error>> {
-    ensure_directories(&meta.name).await?;
-    let output_dir = get_output_dir(&meta.name);
-    let mut hold_components = HashMap::new();
+impl ImageArgs {
+    fn apply(&self, options: &mut ConvertOptions) {
+        options.image = ImageOptions {
+            premultiplied_alpha: self.premultiplied_alpha,
+            preserve_16bit: self.preserve_16bit,
+        };
+    }
+}
 
-    for res in &downloads {
-        let filename = get_filename(&res.res_type);
-        let filepath = output_dir.join(filename);
-        
-        match &res.res_type {
-            ResType::Image(ImageResType::HitFX) => {
-                let processed_data = hit_fx_convector(&res.content)?;
-                save_file(&filepath, Bytes::from(processed_data)).await?;
-            },
-            ResType::Image(img_type) => {
-                match img_type {
-                    ImageResType::HoldEnd | ImageResType::Hold | ImageResType::HoldHead |
-                    ImageResType::HoldHL | ImageResType::HoldHeadHL => {
-                        hold_components.insert(img_type.clone(), res.content.clone());
-                    },
-                    _ => { save_file(&filepath, res.content.clone()).await? },
-                }
-            },
-            _ => {
-                save_file(&filepath, res.content.clone()).await?;
-            }
+impl AudioArgs {
+    fn apply(&self, options: &mut ConvertOptions) -> Result<(), Box<dyn std::error::Error>> {
+        if !(-1.0..=10.0).contains(&self.audio_quality) {
+            return Err(format!("Invalid value for --audio-quality: {}", self.audio_quality).into());
+        }
+        if self.trim_silence.is_some_and(|threshold| threshold >= 0.0) {
+            return Err("--trim-silence must be below 0 dBFS".into());
+        }
+        if self.normalize_peak.is_some_and(|peak| peak > 0.0) {
+            return Err("--normalize-peak must be at most 0 dBFS".into());
+        }
+        if self.sample_rate.is_some_and(|rate| !(8_000..=192_000).contains(&rate)) {
+            return Err("--sample-rate must be between 8000 and 192000".into());
         }
-    }
 
-    if let (Some(end), Some(hold), Some(head)) = (
-        hold_components.get(&ImageResType::HoldEnd),
-        hold_components.get(&ImageResType::Hold),
-        hold_components.get(&ImageResType::HoldHead)
-    ) {
-        let combined = combine_hold_images(end, hold, head)?;
-        save_file(
-            &output_dir.join(get_filename(&ResType::Image(ImageResType::CombinedHold))),
-            Bytes::from(combined)
-        ).await?;
+        options.audio = AudioOptions {
+            quality: self.audio_quality,
+            trim_silence: self.trim_silence,
+            normalize: self.normalize_peak.map(Normalization::Peak).or(self.normalize_lufs.map(Normalization::Lufs)),
+            sample_rate: self.sample_rate,
+            channels: self.channels.map(|channels| match channels {
+                ChannelsArg::Mono => ChannelLayout::Mono,
+                ChannelsArg::Stereo => ChannelLayout::Stereo,
+            }),
+        };
+        Ok(())
     }
+}
 
-    if let (Some(end), Some(hold), Some(head)) = (
-        hold_components.get(&ImageResType::HoldEnd),
-        hold_components.get(&ImageResType::HoldHL),
-        hold_components.get(&ImageResType::HoldHeadHL)
-    ) {
-        let combined = combine_hold_images(end, hold, head)?;
-        save_file(
-            &output_dir.join(get_filename(&ResType::Image(ImageResType::CombinedHoldHL))),
-            Bytes::from(combined)
-        ).await?;
+impl OutputArgs {
+    fn apply(&self, writer: &mut PackWriter) {
+        writer.format = match self.format {
+            FormatArg::Dir => OutputFormat::Dir,
+            FormatArg::Zip => OutputFormat::Zip,
+        };
+        writer.overwrite = self.overwrite;
     }
+}
 
-    let res_info = generate_respack_info(meta, &hold_components)?;
-    let yaml = serde_yaml::to_string(&res_info)?;
-    save_file(
-        &output_dir.join("info.yml"),
-        Bytes::from(yaml.into_bytes())
-    ).await?;
+impl InfoArgs {
+    fn apply(&self, options: &mut ConvertOptions) -> Result<(), ConvertError> {
+        let overrides = InfoOverrides {
+            name: self.name.clone(),
+            author: self.author.clone(),
+            description: self.description.clone(),
+            options: ResPackOptions {
+                hit_fx_duration: self.hit_fx_duration,
+                hit_fx_scale: self.hit_fx_scale,
+                hit_fx_rotate: self.hit_fx_rotate,
+                hit_fx_tinted: self.hit_fx_tinted,
+                hide_particles: self.hide_particles,
+                hold_keep_head: self.hold_keep_head,
+                hold_repeat: self.hold_repeat,
+                hold_compact: self.hold_compact,
+                color_perfect: self.color_perfect,
+                color_good: self.color_good,
+            },
+        };
 
-    Ok(())
+        options.info_overrides = match &self.info_config {
+            Some(path) => InfoOverrides::load(path)?.merge(overrides),
+            None => overrides,
+        };
+        Ok(())
+    }
 }
 
-#[derive(Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ResPackInfo {
+#[derive(Serialize)]
+struct FileSummary {
     name: String,
-    author: String,
+    size: usize,
+}
 
+#[derive(Serialize)]
+struct ConvertSummary {
+    input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    hit_fx: Option<(u32, u32)>,
-    
+    author: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    hold_atlas: Option<(u32, u32)>,
-    #[serde(rename = "holdAtlasMH")]
+    output: Option<PathBuf>,
+    files: Vec<FileSummary>,
+    warnings: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    hold_atlas_mh: Option<(u32, u32)>,
+    report: Option<ConvertReport>,
+    dry_run: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<&'static str>,
+    #[serde(skip)]
+    exit_code: u8,
+}
+
+impl ConvertSummary {
+    fn failed(input: &str, dry_run: bool, error: ConvertError) -> ConvertSummary {
+        ConvertSummary {
+            input: input.to_string(),
+            name: None,
+            author: None,
+            output: None,
+            files: Vec::new(),
+            warnings: Vec::new(),
+            report: None,
+            dry_run,
+            error: Some(error.to_string()),
+            error_kind: Some(error.kind()),
+            exit_code: exit_code(&error),
+        }
+    }
+
+    fn print(&self, verbose: bool) {
+        match (&self.error, &self.output) {
+            (Some(error), _) => eprintln!("Failed to convert {}: {}", self.input, error),
+            (None, Some(output)) if self.dry_run => println!("Would save to {}", output.display()),
+            (None, Some(output)) => println!("Saved to {}", output.display()),
+            (None, None) => println!("{} is valid", self.input),
+        }
+
+        for warning in &self.warnings {
+            eprintln!("Warning ({}): {}", self.input, warning);
+        }
 
-    description: String,
+        if verbose {
+            for file in &self.files {
+                println!("  {} ({})", file.name, format_bytes(file.size as u64));
+            }
+            if let Some(report) = self.report.as_ref().filter(|report| !report.defaults.is_empty()) {
+                println!("  Using prpr defaults for {}", report.defaults.join(", "));
+            }
+        }
+    }
 }
 
-fn get_image_dimensions(data: &[u8]) -> Result<(u32, u32), Box<dyn std::error::Error>> {
-    let img = image::load_from_memory(data)?;
-    Ok((img.width(), img.height()))
+struct Converted {
+    name: String,
+    author: String,
+    files: BTreeMap<String, Bytes>,
+    warnings: Vec<String>,
+    report: Option<ConvertReport>,
 }
 
-fn generate_respack_info(meta: PTRespackMeta, hold_components: &HashMap<ImageResType, Bytes>) -> Result<ResPackInfo, Box<dyn std::error::Error>> {
-    let hold_atlas = if hold_components.contains_key(&ImageResType::HoldEnd) 
-        && hold_components.contains_key(&ImageResType::HoldHead) {
-        let (_, end_height) = get_image_dimensions(hold_components.get(&ImageResType::HoldEnd).unwrap())?;
-        let (_, head_height) = get_image_dimensions(hold_components.get(&ImageResType::HoldHead).unwrap())?;
-        Some((end_height, head_height))
+async fn run_convert(converter: &Converter, writer: Option<&PackWriter>, input: &str, reverse: bool, dry_run: bool) -> ConvertSummary {
+    let converted = if reverse {
+        converter.reverse(Path::new(input)).map(|pack| Converted {
+            name: pack.name,
+            author: pack.meta.author,
+            files: pack.files,
+            warnings: pack.warnings,
+            report: None,
+        })
     } else {
-        None
+        converter.convert(input).await.map(|pack| Converted {
+            name: pack.name,
+            author: pack.info.author,
+            files: pack.files,
+            warnings: pack.report.messages(),
+            report: Some(pack.report),
+        })
+    };
+
+    let Converted { name, author, files, warnings, report } = match converted {
+        Ok(converted) => converted,
+        Err(e) => return ConvertSummary::failed(input, dry_run, e),
+    };
+
+    let output = match writer {
+        None => Ok(None),
+        Some(writer) if dry_run => writer.resolve_path(&name).map(Some),
+        Some(writer) => writer.write(&name, &files).await.map(Some),
+    };
+
+    match output {
+        Ok(output) => ConvertSummary {
+            input: input.to_string(),
+            files: files
+                .iter()
+                .map(|(name, content)| FileSummary { name: name.clone(), size: content.len() })
+                .collect(),
+            name: Some(name),
+            author: Some(author),
+            output,
+            warnings,
+            report,
+            dry_run,
+            error: None,
+            error_kind: None,
+            exit_code: 0,
+        },
+        Err(e) => ConvertSummary::failed(input, dry_run, e),
+    }
+}
+
+fn read_batch_list(list: &str) -> std::io::Result<Vec<String>> {
+    let content = if list == "-" {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        fs::read_to_string(list)?
+    };
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn format_summary_table(summaries: &[ConvertSummary]) -> String {
+    let rows: Vec<[String; 5]> = summaries
+        .iter()
+        .map(|summary| {
+            let status = match (summary.error_kind, summary.dry_run) {
+                (Some(kind), _) => format!("failed ({})", kind),
+                (None, true) => "dry run".to_string(),
+                (None, false) => "ok".to_string(),
+            };
+            [
+                summary.name.clone().unwrap_or_else(|| summary.input.clone()),
+                summary.author.clone().unwrap_or_default(),
+                status,
+                summary.warnings.len().to_string(),
+                summary.output.as_ref().map(|output| output.display().to_string()).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let header = ["NAME", "AUTHOR", "STATUS", "WARNINGS", "OUTPUT"].map(str::to_string);
+    let mut widths = header.clone().map(|cell| cell.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn write_report(path: &Path, summary: &ConvertSummary) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(report) = &summary.report else {
+        return Ok(());
     };
 
-    let hold_atlas_mh = if hold_components.contains_key(&ImageResType::HoldEnd) 
-        && hold_components.contains_key(&ImageResType::HoldHeadHL) {
-        let (_, end_height) = get_image_dimensions(hold_components.get(&ImageResType::HoldEnd).unwrap())?;
-        let (_, head_hl_height) = get_image_dimensions(hold_components.get(&ImageResType::HoldHeadHL).unwrap())?;
-        Some((end_height, head_hl_height))
+    let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let content = if is_json {
+        serde_json::to_string_pretty(report)?
     } else {
-        None
+        report.to_string()
     };
+    fs::write(path, content)?;
+    Ok(())
+}
 
-    Ok(ResPackInfo {
-        name: meta.name,
-        author: meta.author,
-        hit_fx: if hold_components.contains_key(&ImageResType::HitFX) { Some((5, 6)) } else { None },
-        hold_atlas,
-        hold_atlas_mh,
-        description: String::new(),
-    })
-}
-
-pub async fn load_pt_online_respack(url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let meta = fetch_meta(url).await?;
-    let res_urls = res_name_parser(&meta.res);
-    let downloaded = download_res(res_urls).await?;
-    save_res(downloaded, meta).await?;
+fn write_summary(path: &Path, summaries: &[ConvertSummary]) -> Result<(), Box<dyn std::error::Error>> {
+    let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let content = if is_json {
+        serde_json::to_string_pretty(summaries)?
+    } else {
+        format_summary_table(summaries)
+    };
+    fs::write(path, content)?;
     Ok(())
 }
 
-fn main() {
-    let url = env::args().nth(1).unwrap_or_else(|| {
-        eprintln!("Usage: ptonlineres2prpr <url>");
-        eprintln!("No URL provided, using example: {}", PTRESPACK_META_URL);
-        PTRESPACK_META_URL.to_string()
-    });
+fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to serialize output: {}", e),
+    }
+}
+
+fn exit_code(error: &ConvertError) -> u8 {
+    match error {
+        ConvertError::MetaFetch { .. } => 3,
+        ConvertError::Download { .. } => 4,
+        ConvertError::ImageDecode { .. } => 5,
+        ConvertError::Audio { .. } => 6,
+        ConvertError::Io(_) => 7,
+        ConvertError::Validation(_) => 8,
+    }
+}
 
+async fn run(cli: Cli) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let mut options = ConvertOptions {
+        quiet: cli.quiet || cli.json,
+        ..ConvertOptions::default()
+    };
+    let mut writer = PackWriter::default();
+
+    match cli.command {
+        Command::Convert { input, reverse, out, report, output, fetch, info, skin, image, audio } => {
+            output.apply(&mut writer);
+            fetch.apply(&mut options)?;
+            info.apply(&mut options)?;
+            skin.apply(&mut options);
+            image.apply(&mut options);
+            audio.apply(&mut options)?;
+            writer.out = out;
+
+            let converter = Converter::new(options);
+            let summary = run_convert(&converter, Some(&writer), &input, reverse, output.dry_run).await;
+            if cli.json {
+                print_json(&summary);
+            } else {
+                summary.print(cli.verbose);
+            }
+            if let Some(path) = &report {
+                write_report(path, &summary)?;
+            }
+            Ok(ExitCode::from(summary.exit_code))
+        },
+        Command::Inspect { input, fetch } => {
+            fetch.apply(&mut options)?;
+
+            let report = Converter::new(options).inspect(&input).await?;
+            if cli.json {
+                print_json(&report);
+            } else {
+                println!("Name: {}", report.name);
+                println!("Author: {}", report.author);
+                if let Some(description) = &report.description {
+                    println!("Description: {}", description);
+                }
+                for res in &report.resources {
+                    let res_type = res.res_type.as_deref().unwrap_or("unrecognised");
+                    match &res.alias {
+                        Some(alias) => println!("  {} -> {} ({}, as {})", res.key, res.url, res_type, alias),
+                        None => println!("  {} -> {} ({})", res.key, res.url, res_type),
+                    }
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        },
+        Command::Validate { input, report, fetch, info, skin, image, audio } => {
+            fetch.apply(&mut options)?;
+            info.apply(&mut options)?;
+            skin.apply(&mut options);
+            image.apply(&mut options);
+            audio.apply(&mut options)?;
+
+            let converter = Converter::new(options);
+            let summary = run_convert(&converter, None, &input, false, false).await;
+            if cli.json {
+                print_json(&summary);
+            } else {
+                summary.print(cli.verbose);
+            }
+            if let Some(path) = &report {
+                write_report(path, &summary)?;
+            }
+            Ok(ExitCode::from(summary.exit_code))
+        },
+        Command::Batch { list, out_dir, parallel, summary, output, fetch, info, skin, image, audio } => {
+            if parallel == 0 {
+                return Err("--parallel must be at least 1".into());
+            }
+            output.apply(&mut writer);
+            fetch.apply(&mut options)?;
+            info.apply(&mut options)?;
+            skin.apply(&mut options);
+            image.apply(&mut options);
+            audio.apply(&mut options)?;
+            writer.output_root = out_dir;
+            // Progress lines of packs converted at the same time would overwrite each other.
+            options.quiet |= parallel > 1;
+
+            let inputs = read_batch_list(&list)?;
+            let converter = Converter::new(options);
+            let writer = Arc::new(writer);
+            let permits = Arc::new(Semaphore::new(parallel));
+            let dry_run = output.dry_run;
+
+            let tasks: Vec<_> = inputs
+                .into_iter()
+                .map(|input| {
+                    let (converter, writer, permits) = (converter.clone(), writer.clone(), permits.clone());
+                    let task = tokio::spawn({
+                        let input = input.clone();
+                        async move {
+                            let _permit = permits.acquire_owned().await.expect("semaphore is never closed");
+                            run_convert(&converter, Some(&writer), &input, false, dry_run).await
+                        }
+                    });
+                    (input, task)
+                })
+                .collect();
+
+            let mut summaries = Vec::new();
+            for (input, task) in tasks {
+                // A pack that panics is reported like any other failure instead of ending the batch.
+                let result = task
+                    .await
+                    .unwrap_or_else(|e| ConvertSummary::failed(&input, dry_run, ConvertError::Io(e.into())));
+                if !cli.json {
+                    result.print(cli.verbose);
+                }
+                summaries.push(result);
+            }
+
+            if cli.json {
+                print_json(&summaries);
+            } else {
+                println!();
+                print!("{}", format_summary_table(&summaries));
+            }
+            if let Some(path) = &summary {
+                write_summary(path, &summaries)?;
+            }
+            if summaries.iter().all(|summary| summary.error.is_none()) {
+                Ok(ExitCode::SUCCESS)
+            } else {
+                Ok(ExitCode::FAILURE)
+            }
+        },
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
     let runtime = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-    
-    if let Err(e) = runtime.block_on(load_pt_online_respack(&url)) {
-        eprintln!("Error occurred: {}", e);
+
+    match runtime.block_on(run(cli)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error occurred: {}", e);
+            match e.downcast_ref::<ConvertError>() {
+                Some(error) => ExitCode::from(exit_code(error)),
+                None => ExitCode::FAILURE,
+            }
+        },
     }
-}
\ No newline at end of file
+}
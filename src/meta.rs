@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::alias::{normalize_res_name, ResAliases};
+use crate::info::ResPackOptions;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PTRespackMeta {
+    pub name: String,
+    pub author: String,
+    // includes_hit_songs: bool,
+    pub res: BTreeMap<String, String>,
+
+    #[serde(rename = "hitFxFrames")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_fx_frames: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub options: ResPackOptions,
+}
+
+#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+pub enum ImageResType {
+    HitFX,
+    Tap,
+    TapHL,
+    HoldEnd,
+    HoldEndHL,
+    Hold,
+    HoldHL,
+    HoldHead,
+    HoldHeadHL,
+    CombinedHold,
+    CombinedHoldHL,
+    Drag,
+    DragHL,
+    Flick,
+    FlickHL,
+}
+#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+pub enum AudioResType {
+    TapHitSound,
+    DragHitSound,
+    FlickHitSound,
+}
+#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+pub enum ResType {
+    Image(ImageResType),
+    Audio(AudioResType),
+}
+pub(crate) const IMAGE_RES_MAPPINGS: [(&[&str], ImageResType); 13] = [
+    (&["clickraw", "clickraw.png"], ImageResType::HitFX),
+    (&["tap", "tap.png"], ImageResType::Tap),
+    (&["taphl", "taphl.png"], ImageResType::TapHL),
+    (&["holdend", "holdend.png"], ImageResType::HoldEnd),
+    (&["holdendhl", "holdendhl.png"], ImageResType::HoldEndHL),
+    (&["hold", "hold.png"], ImageResType::Hold),
+    (&["holdhl", "holdhl.png"], ImageResType::HoldHL),
+    (&["holdhead", "holdhead.png"], ImageResType::HoldHead),
+    (&["holdheadhl", "holdheadhl.png"], ImageResType::HoldHeadHL),
+    (&["drag", "drag.png"], ImageResType::Drag),
+    (&["draghl", "draghl.png"], ImageResType::DragHL),
+    (&["flick", "flick.png"], ImageResType::Flick),
+    (&["flickhl", "flickhl.png"], ImageResType::FlickHL),
+];
+
+pub(crate) const AUDIO_RES_MAPPINGS: [(&[&str], AudioResType); 3] = [
+    (&["hitsong0", "hitsong0.ogg"], AudioResType::TapHitSound),
+    (&["hitsong1", "hitsong1.ogg"], AudioResType::DragHitSound),
+    (&["hitsong2", "hitsong2.ogg"], AudioResType::FlickHitSound),
+];
+
+#[derive(Debug, Clone)]
+pub(crate) struct ResEntry {
+    pub(crate) key: String,
+    pub(crate) url: String,
+    pub(crate) alias: Option<String>,
+}
+
+fn is_builtin_name(name: &str) -> bool {
+    IMAGE_RES_MAPPINGS.iter().any(|(names, _)| names.contains(&name))
+        || AUDIO_RES_MAPPINGS.iter().any(|(names, _)| names.contains(&name))
+}
+
+/// How strongly a key claims its resource when several keys map to the same one: the
+/// built-in name as written, then the built-in name in another case, then anything else.
+fn name_priority(name: &str) -> u8 {
+    if is_builtin_name(name) {
+        2
+    } else if is_builtin_name(&name.to_lowercase()) {
+        1
+    } else {
+        0
+    }
+}
+
+pub(crate) fn builtin_res_type(stem: &str) -> Option<ResType> {
+    let image = IMAGE_RES_MAPPINGS
+        .iter()
+        .find(|(names, _)| names.iter().any(|name| normalize_res_name(name).0 == stem))
+        .map(|(_, img_type)| ResType::Image(img_type.clone()));
+    let audio = || AUDIO_RES_MAPPINGS
+        .iter()
+        .find(|(names, _)| names.iter().any(|name| normalize_res_name(name).0 == stem))
+        .map(|(_, audio_type)| ResType::Audio(audio_type.clone()));
+    image.or_else(audio)
+}
+
+/// The resource a `res` key names, with the name or alias it matched, regardless of other keys.
+pub(crate) fn match_res_name(name: &str, aliases: &ResAliases) -> Option<(ResType, String)> {
+    let (stem, kind) = normalize_res_name(name);
+    let matched = match builtin_res_type(&stem) {
+        Some(res_type) => Some((res_type, stem)),
+        None => aliases.find(&stem).map(|(res_type, alias)| (res_type, alias.to_string())),
+    };
+    matched.filter(|(res_type, _)| kind.is_none_or(|kind| kind.allows(res_type)))
+}
+
+pub(crate) fn res_name_parser(res: &BTreeMap<String, String>, aliases: &ResAliases) -> HashMap<ResType, ResEntry> {
+    let mut res_urls = HashMap::<ResType, ResEntry>::new();
+    
+    for (name, url) in res {
+        let Some((res_type, alias)) = match_res_name(name, aliases) else {
+            continue;
+        };
+
+        let priority = name_priority(name);
+        let entry = ResEntry {
+            key: name.clone(),
+            url: url.clone(),
+            alias: (priority == 0).then_some(alias),
+        };
+
+        // On a tie the first key in sorted order wins.
+        let keep_existing = res_urls
+            .get(&res_type)
+            .is_some_and(|existing| name_priority(&existing.key) >= priority);
+        if !keep_existing {
+            res_urls.insert(res_type, entry);
+        }
+    }
+
+    res_urls
+}
+
+pub fn get_filename(res_type: &ResType) -> &'static str {
+    match res_type {
+        ResType::Image(img_type) => match img_type {
+            ImageResType::HitFX => "hit_fx.png",
+            ImageResType::Tap => "click.png",
+            ImageResType::TapHL => "click_mh.png",
+            ImageResType::Drag => "drag.png",
+            ImageResType::DragHL => "drag_mh.png",
+            ImageResType::Flick => "flick.png",
+            ImageResType::FlickHL => "flick_mh.png",
+            ImageResType::CombinedHold => "hold.png",
+            ImageResType::CombinedHoldHL => "hold_mh.png",
+            
+            _ => "",
+        },
+        ResType::Audio(audio_type) => match audio_type {
+            AudioResType::TapHitSound => "click.ogg",
+            AudioResType::DragHitSound => "drag.ogg",
+            AudioResType::FlickHitSound => "flick.ogg",
+        },
+    }
+}
+
+pub(crate) const PRPR_PASSTHROUGH_RES: [ResType; 9] = [
+    ResType::Image(ImageResType::Tap),
+    ResType::Image(ImageResType::TapHL),
+    ResType::Image(ImageResType::Drag),
+    ResType::Image(ImageResType::DragHL),
+    ResType::Image(ImageResType::Flick),
+    ResType::Image(ImageResType::FlickHL),
+    ResType::Audio(AudioResType::TapHitSound),
+    ResType::Audio(AudioResType::DragHitSound),
+    ResType::Audio(AudioResType::FlickHitSound),
+];
+
+pub(crate) fn get_pt_res_key(res_type: &ResType) -> Option<&'static str> {
+    let names = match res_type {
+        ResType::Image(img_type) => IMAGE_RES_MAPPINGS
+            .iter()
+            .find(|(_, mapped)| mapped == img_type)
+            .map(|(names, _)| names),
+        ResType::Audio(audio_type) => AUDIO_RES_MAPPINGS
+            .iter()
+            .find(|(_, mapped)| mapped == audio_type)
+            .map(|(names, _)| names),
+    };
+    names.and_then(|names| names.first().copied())
+}
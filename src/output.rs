@@ -0,0 +1,297 @@
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use crate::error::ConvertError;
+use crate::info::INFO_FILENAME;
+
+pub const DEFAULT_OUTPUT_ROOT: &str = "output";
+const DEFAULT_PACK_NAME: &str = "respack";
+const MAX_PACK_NAME_CHARS: usize = 100;
+const MAX_NAME_COLLISIONS: u32 = 999;
+const WINDOWS_RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+pub fn sanitize_pack_name(name: &str) -> Result<String, ConvertError> {
+    if name.split(['/', '\\']).any(|part| part.trim() == "..") {
+        return Err(ConvertError::Validation(format!("Refusing pack name with path traversal: {:?}", name)));
+    }
+
+    let replaced: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let mut slug: String = replaced
+        .trim_matches(|c: char| c.is_whitespace() || c == '.')
+        .chars()
+        .take(MAX_PACK_NAME_CHARS)
+        .collect();
+    slug.truncate(slug.trim_end_matches(|c: char| c.is_whitespace() || c == '.').len());
+
+    if slug.is_empty() {
+        slug = DEFAULT_PACK_NAME.to_string();
+    }
+
+    let stem = slug.split('.').next().unwrap_or_default().to_ascii_uppercase();
+    let is_reserved = WINDOWS_RESERVED_NAMES.contains(&stem.as_str())
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.as_bytes()[3].is_ascii_digit());
+    if is_reserved {
+        slug.insert(0, '_');
+    }
+
+    Ok(slug)
+}
+
+fn get_output_dir(root: &Path, name: &str) -> PathBuf {
+    root.join(name)
+}
+
+fn get_output_zip_path(root: &Path, name: &str) -> PathBuf {
+    root.join(format!("{}.zip", name))
+}
+
+async fn save_file(path: &Path, contents: Bytes) -> std::io::Result<()> {
+    let mut file = File::create(path).await?;
+    file.write_all(&contents).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Dir,
+    Zip,
+}
+
+fn archive_entry_order(files: &BTreeMap<String, Bytes>) -> Vec<(&String, &Bytes)> {
+    let mut entries: Vec<(&String, &Bytes)> = files.iter().collect();
+    entries.sort_by_key(|(filename, _)| (filename.as_str() != INFO_FILENAME, filename.as_str()));
+    entries
+}
+
+pub fn build_respack_zip(files: &BTreeMap<String, Bytes>) -> Result<Vec<u8>, ConvertError> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default())
+        .unix_permissions(0o644);
+
+    for (filename, content) in archive_entry_order(files) {
+        writer.start_file(filename.as_str(), options)?;
+        writer.write_all(content)?;
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
+
+#[derive(Debug, Clone)]
+pub struct PackWriter {
+    pub format: OutputFormat,
+    pub output_root: PathBuf,
+    pub out: Option<PathBuf>,
+    pub overwrite: bool,
+}
+
+impl Default for PackWriter {
+    fn default() -> Self {
+        PackWriter {
+            format: OutputFormat::default(),
+            output_root: PathBuf::from(DEFAULT_OUTPUT_ROOT),
+            out: None,
+            overwrite: false,
+        }
+    }
+}
+
+impl PackWriter {
+    fn output_path(&self, slug: &str) -> PathBuf {
+        match self.format {
+            OutputFormat::Dir => get_output_dir(&self.output_root, slug),
+            OutputFormat::Zip => get_output_zip_path(&self.output_root, slug),
+        }
+    }
+
+    /// The default output path followed by its `-2`, `-3`, ... variants.
+    fn candidate_paths<'a>(&'a self, slug: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+        std::iter::once(self.output_path(slug))
+            .chain((2..=MAX_NAME_COLLISIONS).map(move |suffix| self.output_path(&format!("{}-{}", slug, suffix))))
+    }
+
+    fn already_exists(out: &Path) -> ConvertError {
+        ConvertError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists, pass --overwrite to replace it", out.display()),
+        ))
+    }
+
+    fn too_many_collisions(slug: &str) -> ConvertError {
+        ConvertError::Io(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Too many existing outputs named {}", slug),
+        ))
+    }
+
+    pub fn resolve_path(&self, name: &str) -> Result<PathBuf, ConvertError> {
+        if let Some(out) = &self.out {
+            if out.exists() && !self.overwrite {
+                return Err(Self::already_exists(out));
+            }
+            return Ok(out.clone());
+        }
+
+        let slug = sanitize_pack_name(name)?;
+        let mut paths = self.candidate_paths(&slug);
+        match paths.next() {
+            Some(path) if !path.exists() || self.overwrite => Ok(path),
+            _ => paths.find(|path| !path.exists()).ok_or_else(|| Self::too_many_collisions(&slug)),
+        }
+    }
+
+    /// Creates the output directory or an empty zip file. Unless `replace` is set this fails
+    /// with `AlreadyExists` when the path is taken, so only one writer can claim it; with
+    /// `replace` an existing output is cleared first.
+    fn create_output(&self, path: &Path, replace: bool) -> std::io::Result<Option<fs::File>> {
+        match self.format {
+            OutputFormat::Dir => {
+                if replace {
+                    // Files of an earlier conversion must not outlive the info.yml that named them.
+                    match fs::remove_dir_all(path) {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                        _ => {},
+                    }
+                    fs::create_dir_all(path)?;
+                } else {
+                    fs::create_dir(path)?;
+                }
+                Ok(None)
+            },
+            OutputFormat::Zip => {
+                let file = if replace {
+                    fs::File::create(path)?
+                } else {
+                    fs::OpenOptions::new().write(true).create_new(true).open(path)?
+                };
+                Ok(Some(file))
+            },
+        }
+    }
+
+    /// Picks the output path and creates it in one step, moving on to the next suffix when
+    /// another pack, possibly written at the same time, has taken it.
+    fn claim_output(&self, name: &str) -> Result<(PathBuf, Option<fs::File>), ConvertError> {
+        if let Some(out) = &self.out {
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            return match self.create_output(out, self.overwrite) {
+                Ok(file) => Ok((out.clone(), file)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(Self::already_exists(out)),
+                Err(e) => Err(e.into()),
+            };
+        }
+
+        let slug = sanitize_pack_name(name)?;
+        fs::create_dir_all(&self.output_root)?;
+        for (index, path) in self.candidate_paths(&slug).enumerate() {
+            match self.create_output(&path, self.overwrite && index == 0) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(Self::too_many_collisions(&slug))
+    }
+
+    pub async fn write(&self, name: &str, files: &BTreeMap<String, Bytes>) -> Result<PathBuf, ConvertError> {
+        match self.format {
+            OutputFormat::Dir => {
+                let (output_path, _) = self.claim_output(name)?;
+                for (filename, content) in files {
+                    save_file(&output_path.join(filename), content.clone()).await?;
+                }
+                Ok(output_path)
+            },
+            OutputFormat::Zip => {
+                let archive = build_respack_zip(files)?;
+                let (zip_path, file) = self.claim_output(name)?;
+                if let Some(mut file) = file {
+                    file.write_all(&archive)?;
+                }
+                Ok(zip_path)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ptonlineres2prpr-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn pack(files: &[&str]) -> BTreeMap<String, Bytes> {
+        files.iter().map(|name| (name.to_string(), Bytes::from_static(b"x"))).collect()
+    }
+
+    #[test]
+    fn concurrent_packs_with_the_same_name_get_distinct_paths() {
+        let root = scratch_dir("claim");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        for format in [OutputFormat::Dir, OutputFormat::Zip] {
+            let writer = std::sync::Arc::new(PackWriter {
+                format,
+                output_root: root.clone(),
+                ..PackWriter::default()
+            });
+            let mut paths: Vec<PathBuf> = runtime.block_on(async {
+                let tasks: Vec<_> = (0..8)
+                    .map(|_| {
+                        let writer = writer.clone();
+                        tokio::spawn(async move { writer.write("fish", &pack(&[INFO_FILENAME])).await.unwrap() })
+                    })
+                    .collect();
+                let mut paths = Vec::new();
+                for task in tasks {
+                    paths.push(task.await.unwrap());
+                }
+                paths
+            });
+            paths.sort();
+            paths.dedup();
+            assert_eq!(paths.len(), 8);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn overwriting_a_directory_removes_stale_files() {
+        let root = scratch_dir("overwrite");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let writer = PackWriter {
+            output_root: root.clone(),
+            overwrite: true,
+            ..PackWriter::default()
+        };
+
+        runtime.block_on(writer.write("fish", &pack(&[INFO_FILENAME, "hit_fx.png"]))).unwrap();
+        let path = runtime.block_on(writer.write("fish", &pack(&[INFO_FILENAME]))).unwrap();
+
+        assert_eq!(path, root.join("fish"));
+        assert!(path.join(INFO_FILENAME).exists());
+        assert!(!path.join("hit_fx.png").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+pub(crate) struct Progress {
+    quiet: bool,
+    interactive: bool,
+    total_files: usize,
+    done_files: AtomicUsize,
+    total_bytes: AtomicU64,
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+impl Progress {
+    pub(crate) fn new(total_files: usize, quiet: bool) -> Progress {
+        Progress {
+            quiet,
+            interactive: std::io::stderr().is_terminal(),
+            total_files,
+            done_files: AtomicUsize::new(0),
+            total_bytes: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn update(&self, key: &str, file_bytes: u64, file_total: Option<u64>, chunk_len: u64) {
+        let total_bytes = self.total_bytes.fetch_add(chunk_len, Ordering::Relaxed) + chunk_len;
+        if self.quiet || !self.interactive {
+            return;
+        }
+
+        let file_progress = match file_total {
+            Some(file_total) => format!("{} / {}", format_bytes(file_bytes), format_bytes(file_total)),
+            None => format_bytes(file_bytes),
+        };
+        eprint!(
+            "\r\x1b[2K[{}/{}] {}: {} | {} total",
+            self.done_files.load(Ordering::Relaxed), self.total_files, key, file_progress, format_bytes(total_bytes)
+        );
+    }
+
+    pub(crate) fn finish_file(&self, key: &str, file_bytes: u64) {
+        let done_files = self.done_files.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.quiet {
+            return;
+        }
+
+        let clear = if self.interactive { "\r\x1b[2K" } else { "" };
+        eprintln!("{}[{}/{}] {} ({})", clear, done_files, self.total_files, key, format_bytes(file_bytes));
+    }
+
+    pub(crate) fn discard(&self, bytes: u64) {
+        self.total_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn finish(&self) {
+        if !self.quiet {
+            eprintln!(
+                "Downloaded {} files, {}",
+                self.done_files.load(Ordering::Relaxed),
+                format_bytes(self.total_bytes.load(Ordering::Relaxed))
+            );
+        }
+    }
+}
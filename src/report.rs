@@ -0,0 +1,179 @@
+use bytes::Bytes;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::alias::ResAliases;
+use crate::meta::{get_filename, get_pt_res_key, match_res_name, AudioResType, ImageResType, ResEntry, ResType};
+
+pub(crate) const REQUIRED_RES: [ResType; 10] = [
+    ResType::Image(ImageResType::Tap),
+    ResType::Image(ImageResType::Drag),
+    ResType::Image(ImageResType::Flick),
+    ResType::Image(ImageResType::HoldEnd),
+    ResType::Image(ImageResType::Hold),
+    ResType::Image(ImageResType::HoldHead),
+    ResType::Image(ImageResType::HitFX),
+    ResType::Audio(AudioResType::TapHitSound),
+    ResType::Audio(AudioResType::DragHitSound),
+    ResType::Audio(AudioResType::FlickHitSound),
+];
+
+const PRPR_OUTPUT_RES: [ResType; 12] = [
+    ResType::Image(ImageResType::Tap),
+    ResType::Image(ImageResType::TapHL),
+    ResType::Image(ImageResType::Drag),
+    ResType::Image(ImageResType::DragHL),
+    ResType::Image(ImageResType::Flick),
+    ResType::Image(ImageResType::FlickHL),
+    ResType::Image(ImageResType::CombinedHold),
+    ResType::Image(ImageResType::CombinedHoldHL),
+    ResType::Image(ImageResType::HitFX),
+    ResType::Audio(AudioResType::TapHitSound),
+    ResType::Audio(AudioResType::DragHitSound),
+    ResType::Audio(AudioResType::FlickHitSound),
+];
+
+/// A `res` key that was recognised through a case-insensitive, extension-insensitive or
+/// user-configured alias rather than its exact built-in name.
+#[derive(Debug, Clone, Serialize)]
+pub struct AliasMatch {
+    pub key: String,
+    pub resource: String,
+    pub alias: String,
+}
+
+/// What a conversion could not map: unrecognised `res` keys, missing resources and the
+/// prpr files left to the player's defaults.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConvertReport {
+    pub aliases: Vec<AliasMatch>,
+    pub unrecognised: Vec<String>,
+    /// Keys naming a resource that another key already provides.
+    pub duplicates: Vec<String>,
+    pub missing: Vec<String>,
+    pub substituted: Vec<String>,
+    pub generated: Vec<String>,
+    pub defaults: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ConvertReport {
+    pub(crate) fn new(res: &BTreeMap<String, String>, recognised: &HashMap<ResType, ResEntry>, aliases: &ResAliases) -> ConvertReport {
+        let (unrecognised, duplicates) = res
+            .keys()
+            .filter(|key| !recognised.values().any(|entry| &entry.key == *key))
+            .cloned()
+            .partition(|key| match_res_name(key, aliases).is_none());
+        let mut aliases: Vec<AliasMatch> = recognised
+            .iter()
+            .filter_map(|(res_type, entry)| {
+                Some(AliasMatch {
+                    key: entry.key.clone(),
+                    resource: get_pt_res_key(res_type)?.to_string(),
+                    alias: entry.alias.clone()?,
+                })
+            })
+            .collect();
+        aliases.sort_by(|a, b| a.key.cmp(&b.key));
+        let missing = REQUIRED_RES
+            .iter()
+            .filter(|res_type| !recognised.contains_key(*res_type))
+            .filter_map(get_pt_res_key)
+            .map(str::to_string)
+            .collect();
+
+        ConvertReport {
+            aliases,
+            unrecognised,
+            duplicates,
+            missing,
+            ..ConvertReport::default()
+        }
+    }
+
+    pub(crate) fn record_defaults(&mut self, files: &BTreeMap<String, Bytes>) {
+        self.defaults = PRPR_OUTPUT_RES
+            .iter()
+            .map(get_filename)
+            .filter(|filename| !files.contains_key(*filename))
+            .map(str::to_string)
+            .collect();
+    }
+
+    /// One line per unrecognised or duplicate key, missing resource and conversion warning.
+    pub fn messages(&self) -> Vec<String> {
+        let unrecognised = self.unrecognised.iter().map(|key| format!("Unrecognised resource {}", key));
+        let duplicates = self.duplicates.iter().map(|key| format!("Duplicate resource {}, ignored", key));
+        let missing = self.missing.iter().map(|key| {
+            if self.substituted.contains(key) {
+                format!("Missing resource {}, filled from the default skin", key)
+            } else {
+                format!("Missing resource {}", key)
+            }
+        });
+        unrecognised.chain(duplicates).chain(missing).chain(self.warnings.iter().cloned()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+            && self.unrecognised.is_empty()
+            && self.duplicates.is_empty()
+            && self.missing.is_empty()
+            && self.substituted.is_empty()
+            && self.generated.is_empty()
+            && self.defaults.is_empty()
+            && self.warnings.is_empty()
+    }
+}
+
+impl fmt::Display for ConvertReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.aliases.is_empty() {
+            writeln!(f, "Matched aliases:")?;
+            for alias in &self.aliases {
+                writeln!(f, "  {} -> {} (as {})", alias.key, alias.resource, alias.alias)?;
+            }
+        }
+
+        let sections = [
+            ("Unrecognised resources", &self.unrecognised),
+            ("Ignored duplicate keys", &self.duplicates),
+            ("Missing resources", &self.missing),
+            ("Filled from the default skin", &self.substituted),
+            ("Generated highlights", &self.generated),
+            ("Using prpr defaults for", &self.defaults),
+            ("Warnings", &self.warnings),
+        ];
+
+        for (title, items) in sections {
+            if !items.is_empty() {
+                writeln!(f, "{}:", title)?;
+                for item in items {
+                    writeln!(f, "  {}", item)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta::res_name_parser;
+
+    #[test]
+    fn losing_duplicates_are_not_unrecognised() {
+        let res: BTreeMap<String, String> = [("tap", "a.png"), ("Tap.PNG", "b.png"), ("sparkle", "c.png")]
+            .into_iter()
+            .map(|(key, url)| (key.to_string(), url.to_string()))
+            .collect();
+        let aliases = ResAliases::default();
+        let recognised = res_name_parser(&res, &aliases);
+
+        let report = ConvertReport::new(&res, &recognised, &aliases);
+        assert_eq!(report.unrecognised, ["sparkle"]);
+        assert_eq!(report.duplicates, ["Tap.PNG"]);
+    }
+}
@@ -0,0 +1,97 @@
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use bytes::Bytes;
+use reqwest::Url;
+
+use crate::error::BoxError;
+
+const DATA_URI_PREFIX: &str = "data:";
+const SHORT_DATA_URI_CHARS: usize = 48;
+const DATA_URI_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+pub(crate) fn is_data_uri(value: &str) -> bool {
+    value
+        .get(..DATA_URI_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(DATA_URI_PREFIX))
+}
+
+pub(crate) fn decode_data_uri(uri: &str) -> Result<Bytes, BoxError> {
+    let (header, data) = uri[DATA_URI_PREFIX.len()..]
+        .split_once(',')
+        .ok_or("Data URI has no ',' before its data")?;
+    let is_base64 = header
+        .rsplit(';')
+        .next()
+        .is_some_and(|param| param.trim().eq_ignore_ascii_case("base64"));
+
+    let decoded: Vec<u8> = percent_encoding::percent_decode_str(data).collect();
+    if !is_base64 {
+        return Ok(Bytes::from(decoded));
+    }
+
+    let encoded: Vec<u8> = decoded.into_iter().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    Ok(Bytes::from(DATA_URI_BASE64.decode(encoded)?))
+}
+
+/// Resolves a `res` entry against the URL of the meta it came from; absolute URLs are kept as they are.
+pub(crate) fn resolve_res_url(base: &str, value: &str) -> Result<String, BoxError> {
+    Ok(Url::parse(base)?.join(value)?.to_string())
+}
+
+/// Shortens `data:` URIs for messages, which would otherwise contain the whole file.
+pub(crate) fn display_res_url(value: &str) -> String {
+    if is_data_uri(value) && value.chars().count() > SHORT_DATA_URI_CHARS {
+        format!("{}...", value.chars().take(SHORT_DATA_URI_CHARS).collect::<String>())
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const META_URL: &str = "https://example.com/packs/fish/meta.json";
+
+    #[test]
+    fn detects_data_uris() {
+        assert!(is_data_uri("data:image/png;base64,AAAA"));
+        assert!(is_data_uri("DATA:,x"));
+        assert!(!is_data_uri("tap.png"));
+        assert!(!is_data_uri("da"));
+    }
+
+    #[test]
+    fn decodes_base64_data_uris() {
+        assert_eq!(decode_data_uri("data:text/plain;base64,aGVsbG8=").unwrap(), "hello");
+        assert_eq!(decode_data_uri("data:text/plain;base64,aGVsbG8").unwrap(), "hello");
+        assert_eq!(decode_data_uri("data:text/plain;base64,aGVs\n bG8=").unwrap(), "hello");
+        assert_eq!(decode_data_uri("data:;BASE64,aGVsbG8%3D").unwrap(), "hello");
+    }
+
+    #[test]
+    fn decodes_percent_encoded_data_uris() {
+        assert_eq!(decode_data_uri("data:text/plain,hello%20world").unwrap(), "hello world");
+        assert_eq!(decode_data_uri("data:,%00%FF").unwrap(), &[0x00, 0xff][..]);
+    }
+
+    #[test]
+    fn rejects_data_uris_without_a_comma() {
+        assert!(decode_data_uri("data:text/plain;base64").is_err());
+    }
+
+    #[test]
+    fn resolves_relative_urls_against_the_meta() {
+        assert_eq!(resolve_res_url(META_URL, "tap.png").unwrap(), "https://example.com/packs/fish/tap.png");
+        assert_eq!(resolve_res_url(META_URL, "../x.png").unwrap(), "https://example.com/packs/x.png");
+    }
+
+    #[test]
+    fn keeps_absolute_urls() {
+        let url = "https://cdn.example.org/res/tap.png";
+        assert_eq!(resolve_res_url(META_URL, url).unwrap(), url);
+    }
+}
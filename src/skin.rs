@@ -0,0 +1,132 @@
+use bytes::Bytes;
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::alias::ResAliases;
+use crate::atlas::encode_png;
+use crate::convert::DownloadResult;
+use crate::error::ConvertError;
+use crate::local::{open_local_respack, read_local_res};
+use crate::meta::{res_name_parser, ImageResType, ResType};
+use crate::report::REQUIRED_RES;
+
+const BUILTIN_NOTE_WIDTH: u32 = 256;
+const BUILTIN_NOTE_HEIGHT: u32 = 28;
+const BUILTIN_HOLD_BODY_HEIGHT: u32 = 64;
+const BUILTIN_HOLD_END_HEIGHT: u32 = 8;
+const BUILTIN_HIT_FX_SIZE: u32 = 128;
+const BUILTIN_HIT_FX_FRAMES: u32 = 30;
+
+const TAP_COLOR: Rgba<u8> = Rgba([10, 195, 255, 255]);
+const DRAG_COLOR: Rgba<u8> = Rgba([240, 237, 105, 255]);
+const FLICK_COLOR: Rgba<u8> = Rgba([254, 67, 101, 255]);
+const HOLD_BODY_COLOR: Rgba<u8> = Rgba([10, 195, 255, 200]);
+const HIT_FX_COLOR: [u8; 3] = [255, 236, 160];
+
+/// Where missing resources are taken from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefaultSkin {
+    /// Simple sprites generated by the converter. Hit sounds are not included.
+    Builtin,
+    /// A local PhiTogether pack (meta JSON, directory or `.zip`).
+    Pack(PathBuf),
+}
+
+fn note_sprite(width: u32, height: u32, color: Rgba<u8>) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let is_edge = x < 2 || y < 2 || x + 2 >= width || y + 2 >= height;
+        if is_edge {
+            Rgba([255, 255, 255, color[3]])
+        } else {
+            color
+        }
+    })
+}
+
+fn hit_fx_strip() -> RgbaImage {
+    let size = BUILTIN_HIT_FX_SIZE;
+    let half = size as f32 / 2.0;
+
+    RgbaImage::from_fn(size, size * BUILTIN_HIT_FX_FRAMES, |x, y| {
+        let progress = ((y / size) as f32 + 0.5) / BUILTIN_HIT_FX_FRAMES as f32;
+        let dx = x as f32 + 0.5 - half;
+        let dy = (y % size) as f32 + 0.5 - half;
+        let distance = (dx * dx + dy * dy).sqrt() / half;
+
+        let radius = 0.3 + 0.65 * progress;
+        let thickness = 0.03 + 0.12 * (1.0 - progress);
+        let offset = (distance - radius).abs();
+        let alpha = if offset < thickness {
+            (1.0 - offset / thickness) * (1.0 - progress)
+        } else {
+            0.0
+        };
+
+        let [r, g, b] = HIT_FX_COLOR;
+        Rgba([r, g, b, (alpha * 255.0).round() as u8])
+    })
+}
+
+fn builtin_image(img_type: &ImageResType) -> Option<RgbaImage> {
+    let (width, height) = (BUILTIN_NOTE_WIDTH, BUILTIN_NOTE_HEIGHT);
+    match img_type {
+        ImageResType::Tap | ImageResType::HoldHead => Some(note_sprite(width, height, TAP_COLOR)),
+        ImageResType::Drag => Some(note_sprite(width, height, DRAG_COLOR)),
+        ImageResType::Flick => Some(note_sprite(width, height, FLICK_COLOR)),
+        ImageResType::Hold => Some(RgbaImage::from_pixel(width, BUILTIN_HOLD_BODY_HEIGHT, HOLD_BODY_COLOR)),
+        ImageResType::HoldEnd => Some(note_sprite(width, BUILTIN_HOLD_END_HEIGHT, TAP_COLOR)),
+        ImageResType::HitFX => Some(hit_fx_strip()),
+        _ => None,
+    }
+}
+
+pub(crate) struct SkinResources {
+    pub(crate) resources: Vec<DownloadResult>,
+    pub(crate) hit_fx_frames: Option<u32>,
+}
+
+/// Loads the resources among `missing` that the skin provides.
+pub(crate) fn load_skin_res(skin: &DefaultSkin, missing: &[ResType], aliases: &ResAliases) -> Result<SkinResources, ConvertError> {
+    match skin {
+        DefaultSkin::Builtin => {
+            let mut resources = Vec::new();
+            for res_type in missing {
+                let ResType::Image(img_type) = res_type else {
+                    continue;
+                };
+                if let Some(img) = builtin_image(img_type) {
+                    resources.push(DownloadResult {
+                        res_type: res_type.clone(),
+                        content: Bytes::from(encode_png(&img, img_type)?),
+                        from_pack: false,
+                    });
+                }
+            }
+            Ok(SkinResources {
+                resources,
+                hit_fx_frames: Some(BUILTIN_HIT_FX_FRAMES),
+            })
+        },
+        DefaultSkin::Pack(path) => {
+            let (mut pack, meta) = open_local_respack(path)?;
+            let skin_res: HashMap<_, _> = res_name_parser(&meta.res, aliases)
+                .into_iter()
+                .filter(|(res_type, _)| missing.contains(res_type))
+                .collect();
+            Ok(SkinResources {
+                resources: read_local_res(&mut pack, skin_res)?,
+                hit_fx_frames: meta.hit_fx_frames,
+            })
+        },
+    }
+}
+
+/// The required resources a pack does not provide, which a default skin may fill in.
+pub(crate) fn missing_required_res(downloads: &[DownloadResult]) -> Vec<ResType> {
+    REQUIRED_RES
+        .iter()
+        .filter(|res_type| !downloads.iter().any(|res| &res.res_type == *res_type))
+        .cloned()
+        .collect()
+}